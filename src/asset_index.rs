@@ -0,0 +1,279 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::ManifestError;
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AssetObject {
+    pub hash: String,
+    pub size: u64,
+}
+
+impl AssetObject {
+    /// Where the object is stored: `<assets>/objects/<hash[..2]>/<hash>`.
+    pub fn object_path(&self, assets_path: &Path) -> PathBuf {
+        assets_path
+            .join("objects")
+            .join(self.hash.get(..2).unwrap_or_default())
+            .join(&self.hash)
+    }
+}
+
+/// Whether `hash` is a hex SHA-1, and so safe to build paths and URLs from.
+pub fn is_sha1_hex(hash: &str) -> bool {
+    hash.len() == 40 && hash.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// The asset index a version's `assetIndex` points to.
+///
+/// Deserializing validates every object, see [`read_asset_index_from_str`].
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "RawAssetIndex")]
+pub struct AssetIndex {
+    /// Objects keyed by the path the game knows them by.
+    pub objects: BTreeMap<String, AssetObject>,
+    /// Set by the `legacy` index: the game reads assets from `assets/virtual/<id>/<path>`.
+    #[serde(default, rename = "virtual")]
+    pub virtual_: bool,
+    /// Set by the `pre-1.6` index: the game reads assets from `<game_dir>/resources/<path>`.
+    #[serde(default)]
+    pub map_to_resources: bool,
+}
+
+/// An asset index as found on the wire, before validation.
+#[derive(Deserialize)]
+struct RawAssetIndex {
+    objects: Option<BTreeMap<String, RawAssetObject>>,
+    #[serde(default, rename = "virtual")]
+    virtual_: bool,
+    #[serde(default)]
+    map_to_resources: bool,
+}
+
+#[derive(Deserialize)]
+struct RawAssetObject {
+    hash: Option<String>,
+    size: Option<Value>,
+}
+
+impl TryFrom<RawAssetIndex> for AssetIndex {
+    type Error = ManifestError;
+
+    fn try_from(raw: RawAssetIndex) -> Result<Self, Self::Error> {
+        let objects = raw
+            .objects
+            .ok_or_else(|| ManifestError::MissingField("objects".to_string()))?
+            .into_iter()
+            .map(|(name, object)| {
+                let missing =
+                    |field| ManifestError::MissingField(format!("objects.{name}.{field}"));
+                let hash = object.hash.ok_or_else(|| missing("hash"))?;
+                let size = object.size.ok_or_else(|| missing("size"))?;
+
+                if !is_sha1_hex(&hash) {
+                    return Err(ManifestError::InvalidAssetHash { name, hash });
+                }
+                let Some(size) = size.as_u64() else {
+                    return Err(ManifestError::InvalidAssetSize {
+                        name,
+                        size: size.to_string(),
+                    });
+                };
+                Ok((name, AssetObject { hash, size }))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            objects,
+            virtual_: raw.virtual_,
+            map_to_resources: raw.map_to_resources,
+        })
+    }
+}
+
+/// Parses and validates an asset index: every object needs a 40 character hex hash and a
+/// non-negative integer size.
+pub fn read_asset_index_from_str(string: &str) -> Result<AssetIndex, ManifestError> {
+    let raw: RawAssetIndex = serde_json::from_str(string)?;
+    raw.try_into()
+}
+
+impl AssetIndex {
+    /// The directory old versions read their assets from, if the index uses a legacy layout.
+    pub fn legacy_path(&self, game_path: &Path, id: &str) -> Option<PathBuf> {
+        if self.map_to_resources {
+            Some(game_path.join("resources"))
+        } else if self.virtual_ {
+            Some(game_path.join("assets").join("virtual").join(id))
+        } else {
+            None
+        }
+    }
+
+    /// Lays the downloaded objects out under their own paths in the legacy directory of the
+    /// index `id`, hard linking them where possible and copying them otherwise.
+    ///
+    /// Objects not downloaded yet and files already in place are left alone.
+    pub fn materialize(&self, game_path: &Path, id: &str) -> io::Result<()> {
+        let Some(root) = self.legacy_path(game_path, id) else {
+            return Ok(());
+        };
+        let assets_path = game_path.join("assets");
+
+        for (name, object) in &self.objects {
+            // Names come from the index; never let one climb out of the directory.
+            if !Path::new(name)
+                .components()
+                .all(|c| matches!(c, Component::Normal(_)))
+            {
+                continue;
+            }
+            let source = object.object_path(&assets_path);
+            let target = root.join(name);
+            if !source.is_file()
+                || target
+                    .metadata()
+                    .is_ok_and(|m| m.is_file() && m.len() == object.size)
+            {
+                continue;
+            }
+
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let _ = fs::remove_file(&target);
+            if fs::hard_link(&source, &target).is_err() {
+                fs::copy(&source, &target)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::test_server::{sha1, temp_dir};
+
+    use crate::error::ManifestError;
+
+    use super::{read_asset_index_from_str, AssetIndex, AssetObject};
+
+    fn index(flags: &str, objects: &[(&str, &[u8])]) -> AssetIndex {
+        let objects = objects
+            .iter()
+            .map(|(name, body)| {
+                format!(
+                    r#""{name}": {{"hash": "{}", "size": {}}}"#,
+                    sha1(body),
+                    body.len()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        read_asset_index_from_str(&format!(r#"{{{flags} "objects": {{{objects}}}}}"#)).unwrap()
+    }
+
+    fn store(game: &std::path::Path, body: &[u8]) {
+        let object = AssetObject {
+            hash: sha1(body),
+            size: body.len() as u64,
+        };
+        let path = object.object_path(&game.join("assets"));
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, body).unwrap();
+    }
+
+    #[test]
+    fn flags_default_to_modern_layout() {
+        let modern = index("", &[("minecraft/sounds.json", b"{}")]);
+        assert!(!modern.virtual_ && !modern.map_to_resources);
+        assert!(modern
+            .legacy_path(std::path::Path::new("/game"), "1.20")
+            .is_none());
+
+        let legacy = index(r#""virtual": true,"#, &[]);
+        assert!(legacy.virtual_);
+    }
+
+    #[test]
+    fn materialize_virtual_and_resources() {
+        let game = temp_dir("asset-index");
+        store(&game, b"sound");
+        store(&game, b"lang");
+
+        let legacy = index(
+            r#""virtual": true,"#,
+            &[
+                ("sound/ambient/cave1.ogg", b"sound"),
+                ("lang/en_US.lang", b"lang"),
+                ("missing.txt", b"not downloaded"),
+                ("../escape.txt", b"sound"),
+            ],
+        );
+        legacy.materialize(&game, "legacy").unwrap();
+        let root = game.join("assets/virtual/legacy");
+        assert_eq!(
+            std::fs::read(root.join("sound/ambient/cave1.ogg")).unwrap(),
+            b"sound"
+        );
+        assert_eq!(
+            std::fs::read(root.join("lang/en_US.lang")).unwrap(),
+            b"lang"
+        );
+        assert!(!root.join("missing.txt").exists());
+        assert!(!game.join("assets/virtual/escape.txt").exists());
+
+        let pre_16 = index(
+            r#""map_to_resources": true,"#,
+            &[("sound/step.ogg", b"sound")],
+        );
+        pre_16.materialize(&game, "pre-1.6").unwrap();
+        pre_16.materialize(&game, "pre-1.6").unwrap();
+        assert_eq!(
+            std::fs::read(game.join("resources/sound/step.ogg")).unwrap(),
+            b"sound"
+        );
+
+        std::fs::remove_dir_all(game).unwrap();
+    }
+
+    #[test]
+    fn invalid_objects_are_errors() {
+        let hash = "a".repeat(40);
+        let error = |raw: &str| read_asset_index_from_str(raw).unwrap_err();
+
+        assert!(matches!(
+            error(r#"{"virtual": true}"#),
+            ManifestError::MissingField(f) if f == "objects"
+        ));
+        assert!(matches!(
+            error(r#"{"objects": {"a.ogg": {"size": 1}}}"#),
+            ManifestError::MissingField(f) if f == "objects.a.ogg.hash"
+        ));
+        assert!(matches!(
+            error(r#"{"objects": {"a.ogg": {"hash": "../../../etc", "size": 1}}}"#),
+            ManifestError::InvalidAssetHash { name, .. } if name == "a.ogg"
+        ));
+        assert!(matches!(
+            error(&format!(r#"{{"objects": {{"a.ogg": {{"hash": "{hash}", "size": -1}}}}}}"#)),
+            ManifestError::InvalidAssetSize { size, .. } if size == "-1"
+        ));
+        assert!(matches!(
+            error(r#"{"objects": {"a.ogg": {"hash": "#),
+            ManifestError::Json(_)
+        ));
+
+        // Plain serde goes through the same validation.
+        assert!(serde_json::from_str::<AssetIndex>(
+            r#"{"objects": {"a.ogg": {"hash": "zz", "size": 1}}}"#
+        )
+        .is_err());
+    }
+}
@@ -1,33 +1,102 @@
-use crate::error::{ClientDownloaderError, DownloadError};
+use crate::asset_index::{read_asset_index_from_str, AssetIndex};
+use crate::error::{ClientDownloaderError, DownloadError, JavaDownloadError};
 use crate::launcher_manifest::{LauncherManifest, LauncherManifestVersion};
 use crate::manifest::Manifest;
-use reqwest::blocking::Client;
-use serde_json::Value;
+use crate::rules::{Platform, RuleEnvironment};
+use async_trait::async_trait;
+use reqwest::Client;
 
-use std::path::PathBuf;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
+use super::java_archive::sha256_file;
 use super::{
-    DownloadData, DownloadJava, DownloadResult, DownloadVersion, DownloaderService, Progress,
+    extract_java_archive, extract_natives, runtime_path, AsyncDownloadJava, AsyncDownloadVersion,
+    DownloadCategory, DownloadControl, DownloadData, DownloadJava, DownloadListener,
+    DownloadResult, DownloadVersion, DownloaderService, Encoding, Endpoints, JavaMarker, Mirrors,
+    Progress, RuntimeIndex, RuntimeManifest, SkipPolicy,
 };
 
 pub struct ClientDownloader {
     pub main_manifest: LauncherManifest,
+    pub endpoints: Endpoints,
+    /// Fallback hosts for every download, see [`Mirrors`].
+    pub mirrors: Mirrors,
+    /// Pauses, resumes or cancels the downloads started by this downloader.
+    pub control: DownloadControl,
+    /// Receives per-file events of the downloads started by this downloader.
+    pub listener: Option<Arc<dyn DownloadListener>>,
+    /// How files already on disk are checked before being skipped.
+    pub skip_policy: SkipPolicy,
+    /// The platform to install for, which decides the libraries, natives and Java runtime
+    /// chosen. Defaults to the host.
+    pub platform: Platform,
+}
+
+/// The files installing a version consists of.
+#[derive(Clone, Debug)]
+pub struct InstallPlan {
+    pub downloads: Vec<DownloadData>,
+    /// The asset index of the version, to lay out legacy assets once downloaded.
+    pub asset_index: AssetIndex,
+    /// Natives jars and the entries to leave out, extracted into `natives_path` once
+    /// downloaded.
+    pub natives: Vec<(PathBuf, Vec<String>)>,
+    pub natives_path: PathBuf,
 }
 
 impl ClientDownloader {
     pub fn new() -> Result<Self, ClientDownloaderError> {
-        Ok(Self {
-            main_manifest: Self::init()?,
-        })
+        Self::with_endpoints(Endpoints::default())
+    }
+
+    pub async fn new_async() -> Result<Self, ClientDownloaderError> {
+        Self::with_endpoints_async(Endpoints::default()).await
+    }
+
+    /// Creates a downloader that fetches everything from `endpoints`.
+    pub fn with_endpoints(endpoints: Endpoints) -> Result<Self, ClientDownloaderError> {
+        block_on(Self::with_endpoints_async(endpoints))?
+    }
+
+    pub async fn with_endpoints_async(endpoints: Endpoints) -> Result<Self, ClientDownloaderError> {
+        let main_manifest = Self::init_from_async(&endpoints).await?;
+        Ok(Self::with_manifest(endpoints, main_manifest))
+    }
+
+    /// Creates a downloader around an already fetched version manifest, e.g. a cached one,
+    /// without any request.
+    ///
+    /// [`LauncherManifest::default`] will do when only Java or known manifests are downloaded.
+    pub fn with_manifest(endpoints: Endpoints, main_manifest: LauncherManifest) -> Self {
+        Self {
+            main_manifest,
+            endpoints,
+            mirrors: Mirrors::default(),
+            control: DownloadControl::default(),
+            listener: None,
+            skip_policy: SkipPolicy::default(),
+            platform: Platform::default(),
+        }
     }
 
     pub fn init() -> Result<LauncherManifest, ClientDownloaderError> {
+        block_on(Self::init_async())?
+    }
+
+    pub async fn init_async() -> Result<LauncherManifest, ClientDownloaderError> {
+        Self::init_from_async(&Endpoints::default()).await
+    }
+
+    /// Fetches the launcher version manifest from `endpoints`.
+    pub async fn init_from_async(
+        endpoints: &Endpoints,
+    ) -> Result<LauncherManifest, ClientDownloaderError> {
         let client = Client::new();
-        let response = client
-            .get("https://launchermeta.mojang.com/mc/game/version_manifest.json")
-            .send()?;
+        let response = client.get(&endpoints.version_manifest).send().await?;
 
-        let data: LauncherManifest = serde_json::from_reader(response)?;
+        let data: LauncherManifest = response.json().await?;
         Ok(data)
     }
 
@@ -41,83 +110,24 @@ impl ClientDownloader {
             .iter()
             .find(|v| v.id.eq_ignore_ascii_case(id))
     }
-}
-
-impl DownloadJava for ClientDownloader {
-    fn check_version(&self, root_path: &str, expected_version: &str) -> bool {
-        let mut path = PathBuf::from(root_path);
-        path.push(expected_version);
-
-        path.exists() && path.is_dir()
-    }
 
-    fn download_java(&self, root_path: &str, version: &str, progress: Option<Progress>) {
-        if !self.check_version(root_path, version) {
-            let os = std::env::consts::OS;
-            let arch = std::env::consts::ARCH;
-            let ext = match os {
-                "macos" | "linux" => ".tar.gz",
-                _ => ".zip",
-            };
-            let downloads = vec![DownloadData {
-                url: format!(
-          "https://download.oracle.com/java/{version}/archive/jdk-{version}_{os}-{arch}_bin{ext}"
-        ),
-                file_name: format!("jdk-{version}{ext}"),
-                output_path: format!("jdk-{version}{ext}"),
-                sha1: String::new(),
-                total_size: 0,
-            }];
-            DownloaderService::new(PathBuf::from(root_path))
-                .with_downloads(downloads)
-                .run(progress)
-                .unwrap();
-        }
-    }
-}
-
-impl DownloadVersion for ClientDownloader {
-    fn download_version(
+    /// Works out every file installing `manifest` consists of for [`Self::platform`],
+    /// without downloading anything but the asset index.
+    pub fn plan_by_manifest(
         &self,
-        version_id: &str,
-        game_path: &PathBuf,
-        manifest_path: Option<&PathBuf>,
+        manifest: &Manifest,
+        game_path: &Path,
         version_path: Option<&PathBuf>,
-        progress: Option<Progress>,
-    ) -> Result<Vec<DownloadResult>, ClientDownloaderError> {
-        let version = game_path.clone().join("versions").join(version_id);
-        let manifest_path = manifest_path
-            .unwrap_or(&version.join(format!("{}.json", version_id)))
-            .clone();
-
-        let client = Client::new();
-        let version_option = self.get_version(version_id);
-
-        if version_option.is_none() {
-            return Err(ClientDownloaderError::NoSuchVersion);
-        }
-
-        let version = version_option.unwrap();
-        let response = client.get(&version.url).send()?;
-        let manifest: Manifest = response.json()?;
-        {
-            let response = client.get(&version.url).send()?;
-            let response_str = response.text()?;
-            std::fs::create_dir_all(&game_path)?;
-            std::fs::create_dir_all(&manifest_path.parent().unwrap())?;
-            std::fs::write(manifest_path, response_str)?;
-        }
-
-        self.download_by_manifest(&manifest, game_path, version_path, progress)
+    ) -> Result<InstallPlan, ClientDownloaderError> {
+        block_on(self.plan_by_manifest_async(manifest, game_path, version_path))?
     }
 
-    fn download_by_manifest(
+    pub async fn plan_by_manifest_async(
         &self,
         manifest: &Manifest,
-        game_path: &PathBuf,
+        game_path: &Path,
         version_path: Option<&PathBuf>,
-        progress: Option<Progress>,
-    ) -> Result<Vec<DownloadResult>, ClientDownloaderError> {
+    ) -> Result<InstallPlan, ClientDownloaderError> {
         let version_path = version_path
             .unwrap_or(
                 &game_path
@@ -126,9 +136,6 @@ impl DownloadVersion for ClientDownloader {
                     .join(format!("{}.jar", manifest.id)),
             )
             .clone();
-        std::fs::create_dir_all(&version_path.parent().unwrap())?;
-
-        let client = Client::new();
         let mut downloads: Vec<DownloadData> = Vec::new();
 
         // Add client
@@ -144,67 +151,79 @@ impl DownloadVersion for ClientDownloader {
                 output_path: version_path.as_path().to_str().unwrap().to_string(),
                 sha1: manifest.clone().downloads.client.sha1,
                 total_size: manifest.downloads.client.size,
+                category: DownloadCategory::Client,
+                encoding: Encoding::Identity,
+            });
+        }
+
+        // Add logging configuration
+        if let Some(logging) = manifest.logging.as_ref().and_then(|l| l.client.as_ref()) {
+            let path = logging.config_path(game_path);
+            downloads.push(DownloadData {
+                url: logging.file.url.clone(),
+                file_name: logging.file.id.clone(),
+                output_path: path.to_str().unwrap().to_string(),
+                sha1: logging.file.sha1.clone(),
+                total_size: logging.file.size,
+                category: DownloadCategory::LogConfig,
+                encoding: Encoding::Identity,
             });
         }
 
         // Add asset index
+        let index_path;
         {
-            let mut path = game_path.clone();
+            let mut path = game_path.to_path_buf();
             path.push("assets");
             path.push("indexes");
             path.push(format!("{}.json", manifest.asset_index.id));
 
             let path = path.to_str().unwrap();
-            let size = manifest.asset_index.size as u64;
+            let size = manifest.asset_index.size;
 
-            downloads.push(DownloadData {
+            let download = DownloadData {
                 url: manifest.asset_index.url.clone(),
                 file_name: format!("{}.json", manifest.asset_index.id),
                 output_path: path.to_string(),
                 sha1: manifest.clone().asset_index.sha1,
                 total_size: size,
-            });
+                category: DownloadCategory::AssetIndex,
+                encoding: Encoding::Identity,
+            };
+            // Fetched now, like any file, to list the assets; the run then skips it.
+            index_path = self
+                .download_one(game_path, download.clone(), None)
+                .await?
+                .file_path;
+            downloads.push(download);
         }
 
         // Add assets
+        let asset_index: AssetIndex;
         {
-            let mut path = game_path.clone();
+            let mut path = game_path.to_path_buf();
             path.push("assets");
 
-            let mut objects_path = path.clone();
-            objects_path.push("objects");
-
-            let response = client.get(manifest.clone().asset_index.url).send()?;
-
-            let data: Value = serde_json::from_reader(response)?;
-            let object = data.get("objects").unwrap().as_object().unwrap();
+            asset_index = read_asset_index_from_str(&std::fs::read_to_string(&index_path)?)?;
             downloads.extend(
-                object
+                asset_index
+                    .objects
                     .iter()
-                    .map(|(p, obj)| {
-                        let hash = obj.get("hash").unwrap().as_str().unwrap();
-                        let size = obj.get("size").unwrap().as_u64().unwrap();
-
-                        let mut path = objects_path.clone();
-                        path.push(hash[..2].to_string());
-                        path.push(hash.to_string());
-
-                        DownloadData {
-                            url: format!(
-                                "https://resources.download.minecraft.net/{}/{}",
-                                hash[..2].to_string(),
-                                hash
-                            ),
-                            file_name: p.clone(),
-                            output_path: path.to_str().unwrap().to_string(),
-                            sha1: hash.to_string(),
-                            total_size: size,
-                        }
+                    .map(|(p, object)| DownloadData {
+                        url: self.endpoints.asset_url(&object.hash),
+                        file_name: p.clone(),
+                        output_path: object.object_path(&path).to_str().unwrap().to_string(),
+                        sha1: object.hash.clone(),
+                        total_size: object.size,
+                        category: DownloadCategory::Asset,
+                        encoding: Encoding::Identity,
                     })
                     .collect::<Vec<DownloadData>>(),
             );
         }
 
+        let env = RuleEnvironment::new(self.platform.clone());
+
         // Add libraries to download
         {
             let mut path = game_path.to_path_buf();
@@ -213,6 +232,7 @@ impl DownloadVersion for ClientDownloader {
                 manifest
                     .libraries
                     .iter()
+                    .filter(|l| l.is_allowed(&env))
                     .filter_map(|l| {
                         if let Some(artifact) = l.downloads.artifact.clone() {
                             let mut path = path.clone();
@@ -220,7 +240,10 @@ impl DownloadVersion for ClientDownloader {
                                 path.push(p);
                             }
                             let data = DownloadData {
+                                url: self.endpoints.library_url(&artifact.url),
                                 output_path: path.to_str().unwrap().to_string(),
+                                category: DownloadCategory::Library,
+                                encoding: Encoding::Identity,
                                 ..DownloadData::from(artifact)
                             };
                             return Some(data);
@@ -231,10 +254,339 @@ impl DownloadVersion for ClientDownloader {
             );
         }
 
-        let results = DownloaderService::new(game_path.parent().unwrap().to_path_buf())
+        // Add natives, extracted once downloaded
+        let mut natives = Vec::new();
+        for library in manifest.libraries.iter().filter(|l| l.is_allowed(&env)) {
+            if let Some(artifact) = library.native_artifact(&env.platform).cloned() {
+                let mut path = game_path.join("libraries");
+                if let Some(p) = &artifact.path {
+                    path.push(p);
+                }
+                natives.push((path.clone(), library.native_excludes().to_vec()));
+                downloads.push(DownloadData {
+                    url: self.endpoints.library_url(&artifact.url),
+                    output_path: path.to_str().unwrap().to_string(),
+                    category: DownloadCategory::Native,
+                    encoding: Encoding::Identity,
+                    ..DownloadData::from(artifact)
+                });
+            }
+        }
+
+        Ok(InstallPlan {
+            downloads,
+            asset_index,
+            natives,
+            natives_path: version_path.parent().unwrap().join("natives"),
+        })
+    }
+
+    /// Installs the Java runtime `component` (e.g. `java-runtime-gamma`) Mojang ships for
+    /// [`Self::platform`] into `<root_path>/<component>`, see [`runtime_path`].
+    pub fn download_java_runtime(
+        &self,
+        root_path: &Path,
+        component: &str,
+        progress: Option<Progress>,
+    ) -> Result<Vec<DownloadResult>, ClientDownloaderError> {
+        block_on(self.download_java_runtime_async(root_path, component, progress))?
+    }
+
+    pub async fn download_java_runtime_async(
+        &self,
+        root_path: &Path,
+        component: &str,
+        progress: Option<Progress>,
+    ) -> Result<Vec<DownloadResult>, ClientDownloaderError> {
+        let client = Client::new();
+        let response = client.get(&self.endpoints.java_runtimes).send().await?;
+        let index: RuntimeIndex = response.json().await?;
+        let release = index
+            .release(&self.platform, component)
+            .ok_or_else(|| ClientDownloaderError::NoSuchRuntime(component.to_string()))?;
+
+        // The file manifest goes through the service too, so it is verified like any file.
+        let manifest_download = DownloadData {
+            url: release.manifest.url.clone(),
+            file_name: format!("{component}.json"),
+            output_path: format!("{component}.json"),
+            sha1: release.manifest.sha1.clone(),
+            total_size: release.manifest.size,
+            category: DownloadCategory::Java,
+            encoding: Encoding::Identity,
+        };
+        let manifest_path = self
+            .download_one(root_path, manifest_download, None)
+            .await?
+            .file_path;
+        let manifest: RuntimeManifest =
+            serde_json::from_str(&std::fs::read_to_string(manifest_path)?)?;
+
+        let path = runtime_path(root_path, component);
+        manifest.create_directories(&path)?;
+        let results = self
+            .service(path.clone(), manifest.downloads())
+            .run_async(progress)
+            .await;
+        manifest.finish(&path)?;
+
+        Ok(results)
+    }
+
+    /// Downloads a single file with this downloader's settings.
+    async fn download_one(
+        &self,
+        download_folder: &Path,
+        download: DownloadData,
+        progress: Option<Progress>,
+    ) -> DownloadResult {
+        self.service(download_folder.to_path_buf(), vec![download])
+            .run_async(progress)
+            .await
+            .pop()
+            .unwrap_or_else(|| {
+                Err(DownloadError::DownloadDefinition(
+                    "No Downloaded files".to_string(),
+                ))
+            })
+    }
+
+    /// The SHA-256 the vendor publishes next to the Java archive at `url` as `<url>.sha256`,
+    /// fetched into `root_path` and removed once read.
+    async fn published_sha256(
+        &self,
+        root_path: &Path,
+        url: &str,
+        file_name: &str,
+    ) -> Result<String, JavaDownloadError> {
+        let missing = || JavaDownloadError::MissingChecksum(url.to_string());
+        let file_name = format!("{file_name}.sha256");
+        // A leftover file must not pass for this checksum.
+        let _ = std::fs::remove_file(root_path.join(&file_name));
+        let download = DownloadData {
+            url: format!("{url}.sha256"),
+            file_name: file_name.clone(),
+            output_path: file_name,
+            sha1: String::new(),
+            total_size: 0,
+            category: DownloadCategory::Java,
+            encoding: Encoding::Identity,
+        };
+        let path = self
+            .download_one(root_path, download, None)
+            .await?
+            .file_path;
+        let text = std::fs::read_to_string(&path);
+        let _ = std::fs::remove_file(&path);
+
+        // Some vendors follow the hash with the file name, like `sha256sum` does.
+        text?
+            .split_whitespace()
+            .next()
+            .filter(|hash| hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit()))
+            .map(str::to_ascii_lowercase)
+            .ok_or_else(missing)
+    }
+
+    /// A service for `downloads` configured with this downloader's settings.
+    fn service(&self, download_folder: PathBuf, downloads: Vec<DownloadData>) -> DownloaderService {
+        let mut service = DownloaderService::new(download_folder);
+        service
             .with_downloads(downloads)
-            .run(progress)
-            .unwrap();
+            .with_mirrors(self.mirrors.clone())
+            .with_control(self.control.clone())
+            .with_skip_policy(self.skip_policy.clone());
+        if let Some(listener) = &self.listener {
+            service.with_listener(listener.clone());
+        }
+        service
+    }
+}
+
+impl DownloadJava for ClientDownloader {
+    /// Whether `<root_path>/<expected_version>` holds a Java whose archive was fully
+    /// extracted, see [`JavaMarker`].
+    fn check_version(&self, root_path: &str, expected_version: &str) -> bool {
+        let mut path = PathBuf::from(root_path);
+        path.push(expected_version);
+
+        JavaMarker::read(&path).is_some()
+    }
+
+    fn download_java(
+        &self,
+        root_path: &str,
+        version: &str,
+        progress: Option<Progress>,
+    ) -> Result<(), JavaDownloadError> {
+        block_on(self.download_java_async(root_path, version, progress))?
+    }
+}
+
+#[async_trait]
+impl AsyncDownloadJava for ClientDownloader {
+    async fn download_java_async(
+        &self,
+        root_path: &str,
+        version: &str,
+        progress: Option<Progress>,
+    ) -> Result<(), JavaDownloadError> {
+        if self.check_version(root_path, version) {
+            return Ok(());
+        }
+
+        let (os, arch) = java_platform(&self.platform).ok_or_else(|| {
+            JavaDownloadError::UnsupportedPlatform {
+                os: self.platform.os.clone(),
+                arch: self.platform.arch.clone(),
+            }
+        })?;
+        let ext = match os {
+            "macos" | "linux" => ".tar.gz",
+            _ => ".zip",
+        };
+        let url = self.endpoints.java_url(version, os, arch, ext);
+        let root = Path::new(root_path);
+        let file_name = format!("jdk-{version}{ext}");
+
+        // Vendors publish a checksum for every archive, so a missing one means there is no
+        // such build.
+        let not_found = |e| match e {
+            JavaDownloadError::Download(DownloadError::Download(output))
+                if output.status == 404 =>
+            {
+                JavaDownloadError::NotFound {
+                    version: version.to_string(),
+                    os: os.to_string(),
+                    arch: arch.to_string(),
+                }
+            }
+            e => e,
+        };
+
+        // Fetched first, so no archive is downloaded that couldn't be verified.
+        let expected = self
+            .published_sha256(root, &url, &file_name)
+            .await
+            .map_err(not_found)?;
+        let download = DownloadData {
+            url: url.clone(),
+            file_name: file_name.clone(),
+            output_path: file_name,
+            sha1: String::new(),
+            total_size: 0,
+            category: DownloadCategory::Java,
+            encoding: Encoding::Identity,
+        };
+        let archive = self
+            .download_one(root, download, progress)
+            .await
+            .map_err(|e| not_found(e.into()))?
+            .file_path;
+
+        // An archive that doesn't match is never kept around.
+        let hashed = archive.clone();
+        let actual = blocking(move || sha256_file(&hashed))
+            .await
+            .and_then(|actual| actual);
+        if actual.as_ref().map_or(true, |actual| *actual != expected) {
+            let _ = std::fs::remove_file(&archive);
+        }
+        let actual = actual?;
+        if actual != expected {
+            return Err(JavaDownloadError::ChecksumMismatch {
+                url,
+                expected,
+                actual,
+            });
+        }
+
+        let dir = root.join(version);
+        blocking(move || install_java_archive(&archive, &dir, url, actual)).await??;
+        Ok(())
+    }
+}
+
+impl DownloadVersion for ClientDownloader {
+    fn download_version(
+        &self,
+        version_id: &str,
+        game_path: &Path,
+        manifest_path: Option<&PathBuf>,
+        version_path: Option<&PathBuf>,
+        progress: Option<Progress>,
+    ) -> Result<Vec<DownloadResult>, ClientDownloaderError> {
+        block_on(self.download_version_async(
+            version_id,
+            game_path,
+            manifest_path,
+            version_path,
+            progress,
+        ))?
+    }
+
+    fn download_by_manifest(
+        &self,
+        manifest: &Manifest,
+        game_path: &Path,
+        version_path: Option<&PathBuf>,
+        progress: Option<Progress>,
+    ) -> Result<Vec<DownloadResult>, ClientDownloaderError> {
+        block_on(self.download_by_manifest_async(manifest, game_path, version_path, progress))?
+    }
+}
+
+#[async_trait]
+impl AsyncDownloadVersion for ClientDownloader {
+    async fn download_version_async(
+        &self,
+        version_id: &str,
+        game_path: &Path,
+        manifest_path: Option<&PathBuf>,
+        version_path: Option<&PathBuf>,
+        progress: Option<Progress>,
+    ) -> Result<Vec<DownloadResult>, ClientDownloaderError> {
+        let version = game_path.join("versions").join(version_id);
+        let manifest_path = manifest_path
+            .unwrap_or(&version.join(format!("{}.json", version_id)))
+            .clone();
+
+        let client = Client::new();
+        let version_option = self.get_version(version_id);
+
+        if version_option.is_none() {
+            return Err(ClientDownloaderError::NoSuchVersion);
+        }
+
+        let version = version_option.unwrap();
+        let response_str = client.get(&version.url).send().await?.text().await?;
+        let manifest: Manifest = serde_json::from_str(&response_str)?;
+        {
+            std::fs::create_dir_all(game_path)?;
+            std::fs::create_dir_all(manifest_path.parent().unwrap())?;
+            std::fs::write(manifest_path, response_str)?;
+        }
+
+        self.download_by_manifest_async(&manifest, game_path, version_path, progress)
+            .await
+    }
+
+    async fn download_by_manifest_async(
+        &self,
+        manifest: &Manifest,
+        game_path: &Path,
+        version_path: Option<&PathBuf>,
+        progress: Option<Progress>,
+    ) -> Result<Vec<DownloadResult>, ClientDownloaderError> {
+        let plan = self
+            .plan_by_manifest_async(manifest, game_path, version_path)
+            .await?;
+        std::fs::create_dir_all(plan.natives_path.parent().unwrap())?;
+
+        let results = self
+            .service(game_path.parent().unwrap().to_path_buf(), plan.downloads)
+            .run_async(progress)
+            .await;
 
         if results.is_empty() {
             return Err(ClientDownloaderError::Download(
@@ -242,6 +594,504 @@ impl DownloadVersion for ClientDownloader {
             ));
         }
 
+        let natives = plan
+            .natives
+            .into_iter()
+            .filter(|(jar, _)| results.iter().flatten().any(|r| r.file_path == *jar))
+            .collect::<Vec<_>>();
+        let (natives_path, asset_index) = (plan.natives_path, plan.asset_index);
+        let (game_path, id) = (game_path.to_path_buf(), manifest.asset_index.id.clone());
+        blocking(move || -> Result<(), ClientDownloaderError> {
+            for (jar, exclude) in natives {
+                extract_natives(&jar, &natives_path, &exclude)?;
+            }
+            asset_index.materialize(&game_path, &id)?;
+            Ok(())
+        })
+        .await??;
+
         Ok(results)
     }
 }
+
+/// Extracts a downloaded Java `archive` into `dir`, marks it as installed and removes the
+/// archive.
+fn install_java_archive(
+    archive: &Path,
+    dir: &Path,
+    url: String,
+    sha256: String,
+) -> Result<(), ClientDownloaderError> {
+    extract_java_archive(archive, dir)?;
+    JavaMarker { url, sha256 }.write(dir)?;
+    std::fs::remove_file(archive)?;
+    Ok(())
+}
+
+/// OS and architecture of `platform` as named in Java archive URLs, if builds exist for it.
+fn java_platform(platform: &Platform) -> Option<(&'static str, &'static str)> {
+    let os = match platform.os.as_str() {
+        "linux" => "linux",
+        "osx" => "macos",
+        "windows" => "windows",
+        _ => return None,
+    };
+    let arch = match platform.arch.as_str() {
+        "arm64" => "aarch64",
+        "x86_64" => "x64",
+        _ => return None,
+    };
+    Some((os, arch))
+}
+
+/// Runs filesystem work like hashing or extracting on the blocking pool, keeping it off the
+/// executor.
+async fn blocking<T: Send + 'static>(
+    work: impl FnOnce() -> T + Send + 'static,
+) -> std::io::Result<T> {
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(std::io::Error::other)
+}
+
+/// Drives `future` to completion on a runtime owned by the blocking API.
+pub(crate) fn block_on<F: Future>(future: F) -> Result<F::Output, ClientDownloaderError> {
+    Ok(tokio::runtime::Runtime::new()?.block_on(future))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{
+        client::{
+            test_server::{serve_java_runtime, sha1, tar_gz, temp_dir, zip, TestServer},
+            AsyncDownloadVersion, DownloadCategory, DownloadJava, DownloadVersion, Endpoints,
+            JavaMarker,
+        },
+        error::{ClientDownloaderError, JavaDownloadError},
+        manifest::{read_manifest_from_str, Manifest},
+        rules::Platform,
+    };
+
+    use super::{java_platform, ClientDownloader};
+
+    const LEGACY: &str = include_str!("../../tests/fixtures/versions/1.12.2.json");
+
+    const CLIENT_JAR: &[u8] = b"client jar";
+    const LIBRARY: &[u8] = b"library jar";
+    const ASSET: &[u8] = b"asset object";
+
+    /// Serves a `test` version with a library, natives and an asset, returning the
+    /// endpoints pointing at it.
+    fn serve_test_version(server: &TestServer) -> Endpoints {
+        let url = server.url().to_string();
+
+        let natives = zip(&[
+            ("META-INF/MANIFEST.MF", b"manifest"),
+            ("libnative.so", b"native"),
+        ]);
+        let native = |os: &str| {
+            format!(
+                r#""natives-{os}": {{
+                    "path": "com/example/native/1.0/native-1.0-natives.jar",
+                    "sha1": "{}",
+                    "size": {},
+                    "url": "https://libraries.minecraft.net/com/example/native/1.0/native-1.0-natives.jar"
+                }}"#,
+                sha1(&natives),
+                natives.len()
+            )
+        };
+        let asset_hash = sha1(ASSET);
+        let asset_index = format!(
+            r#"{{"objects":{{"minecraft/lang/en_us.json":{{"hash":"{asset_hash}","size":{}}}}}}}"#,
+            ASSET.len()
+        );
+        let version = format!(
+            r#"{{
+                "assetIndex": {{"id": "1", "sha1": "{}", "size": {}, "totalSize": {}, "url": "{url}/index.json"}},
+                "assets": "1",
+                "complianceLevel": 1,
+                "downloads": {{
+                    "client": {{"sha1": "{}", "size": {}, "url": "{url}/client.jar"}},
+                    "server": {{"sha1": "", "size": 0, "url": "{url}/server.jar"}}
+                }},
+                "id": "test",
+                "javaVersion": {{"component": "java-runtime-gamma", "majorVersion": 17}},
+                "libraries": [{{
+                    "downloads": {{"artifact": {{
+                        "path": "com/example/lib/1.0/lib-1.0.jar",
+                        "sha1": "{}",
+                        "size": {},
+                        "url": "https://libraries.minecraft.net/com/example/lib/1.0/lib-1.0.jar"
+                    }}}},
+                    "name": "com.example:lib:1.0"
+                }}, {{
+                    "downloads": {{"classifiers": {{{}, {}, {}}}}},
+                    "name": "com.example:native:1.0",
+                    "natives": {{"linux": "natives-linux", "osx": "natives-osx", "windows": "natives-windows"}}
+                }}, {{
+                    "downloads": {{"artifact": {{
+                        "path": "com/example/elsewhere/1.0/elsewhere-1.0.jar",
+                        "sha1": "", "size": 0, "url": "{url}/missing.jar"
+                    }}}},
+                    "name": "com.example:elsewhere:1.0",
+                    "rules": [{{"action": "disallow"}}]
+                }}],
+                "mainClass": "net.minecraft.client.main.Main",
+                "minimumLauncherVersion": 21,
+                "releaseTime": "2023-03-14T12:56:18+00:00",
+                "time": "2023-03-14T12:56:18+00:00",
+                "type": "release"
+            }}"#,
+            sha1(asset_index.as_bytes()),
+            asset_index.len(),
+            ASSET.len(),
+            sha1(CLIENT_JAR),
+            CLIENT_JAR.len(),
+            sha1(LIBRARY),
+            LIBRARY.len(),
+            native("linux"),
+            native("osx"),
+            native("windows"),
+        );
+        let version_manifest = format!(
+            r#"{{
+                "latest": {{"release": "test", "snapshot": "test"}},
+                "versions": [{{
+                    "id": "test",
+                    "releaseTime": "2023-03-14T12:56:18+00:00",
+                    "time": "2023-03-14T12:56:18+00:00",
+                    "url": "{url}/test.json",
+                    "type": "release"
+                }}]
+            }}"#
+        );
+
+        server.serve("/version_manifest.json", version_manifest.as_bytes());
+        server.serve("/test.json", version.as_bytes());
+        server.serve("/index.json", asset_index.as_bytes());
+        server.serve("/client.jar", CLIENT_JAR);
+        server.serve("/maven/com/example/lib/1.0/lib-1.0.jar", LIBRARY);
+        server.serve(
+            "/maven/com/example/native/1.0/native-1.0-natives.jar",
+            &natives,
+        );
+        server.serve(
+            &format!("/objects/{}/{asset_hash}", &asset_hash[..2]),
+            ASSET,
+        );
+
+        Endpoints {
+            version_manifest: format!("{url}/version_manifest.json"),
+            assets: format!("{url}/objects"),
+            libraries: format!("{url}/maven"),
+            java: format!("{url}/java"),
+            java_runtimes: format!("{url}/java-runtimes.json"),
+        }
+    }
+
+    #[test]
+    fn download_version_from_local_endpoints() {
+        let server = TestServer::start();
+        let endpoints = serve_test_version(&server);
+        let downloader = ClientDownloader::with_endpoints(endpoints).unwrap();
+
+        let dir = temp_dir("endpoints");
+        let game_path = dir.join(".minecraft");
+        let results = downloader
+            .download_version("test", &game_path, None, None, None)
+            .unwrap();
+
+        assert!(results.iter().all(|r| r.is_ok()));
+        assert_eq!(
+            std::fs::read(game_path.join("versions/test/test.jar")).unwrap(),
+            CLIENT_JAR
+        );
+        assert_eq!(
+            std::fs::read(game_path.join("libraries/com/example/lib/1.0/lib-1.0.jar")).unwrap(),
+            LIBRARY
+        );
+        assert_eq!(
+            std::fs::read(
+                game_path
+                    .join("assets/objects")
+                    .join(&sha1(ASSET)[..2])
+                    .join(sha1(ASSET))
+            )
+            .unwrap(),
+            ASSET
+        );
+        assert_eq!(
+            std::fs::read(game_path.join("versions/test/natives/libnative.so")).unwrap(),
+            b"native"
+        );
+        assert!(!game_path.join("versions/test/natives/META-INF").exists());
+        assert!(!game_path
+            .join("libraries/com/example/elsewhere/1.0/elsewhere-1.0.jar")
+            .exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn download_version_inside_runtime() {
+        let server = TestServer::start();
+        let downloader = ClientDownloader::with_endpoints_async(serve_test_version(&server))
+            .await
+            .unwrap();
+
+        let dir = temp_dir("endpoints-async");
+        let game_path = dir.join(".minecraft");
+        let results = downloader
+            .download_version_async("test", &game_path, None, None, None)
+            .await
+            .unwrap();
+        assert!(results.iter().all(|r| r.is_ok()));
+        assert_eq!(
+            std::fs::read(game_path.join("versions/test/test.jar")).unwrap(),
+            CLIENT_JAR
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// A downloader and the legacy fixture, its asset index served empty by `server`.
+    fn legacy_plan_setup(server: &TestServer) -> (ClientDownloader, Manifest) {
+        let url = server.url().to_string();
+        let index = br#"{"objects": {}}"#;
+        server.serve("/index.json", index);
+
+        let mut raw: serde_json::Value = serde_json::from_str(LEGACY).unwrap();
+        raw["assetIndex"]["url"] = format!("{url}/index.json").into();
+        raw["assetIndex"]["sha1"] = sha1(index).into();
+        raw["assetIndex"]["size"] = index.len().into();
+        let manifest = read_manifest_from_str(&raw.to_string()).unwrap();
+
+        let downloader = ClientDownloader::with_manifest(Endpoints::default(), Default::default());
+        (downloader, manifest)
+    }
+
+    #[test]
+    fn plan_follows_target_platform() {
+        let server = TestServer::start();
+        let (mut downloader, manifest) = legacy_plan_setup(&server);
+        let dir = temp_dir("plan-platform");
+        let mut plan = |platform: Platform| {
+            downloader.platform = platform;
+            let plan = downloader.plan_by_manifest(&manifest, &dir, None).unwrap();
+            plan.downloads
+                .iter()
+                .filter(|d| {
+                    matches!(
+                        d.category(),
+                        DownloadCategory::Library | DownloadCategory::Native
+                    )
+                })
+                .map(|d| d.file_name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(plan(Platform::new("linux", "x86_64")), ["patchy-1.3.9.jar"]);
+        assert_eq!(
+            plan(Platform::new("windows", "x86")),
+            [
+                "patchy-1.3.9.jar",
+                "twitch-external-platform-4.5-natives-windows-32.jar"
+            ]
+        );
+        assert_eq!(
+            plan(Platform::new("osx", "arm64")),
+            ["patchy-1.3.9.jar", "java-objc-bridge-1.0.0.jar"]
+        );
+        // The asset index was fetched once and reused by the later plans.
+        assert_eq!(server.requests("/index.json").len(), 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn plan_includes_logging_config() {
+        let server = TestServer::start();
+        let (downloader, manifest) = legacy_plan_setup(&server);
+        let dir = temp_dir("plan-logging");
+
+        let plan = downloader.plan_by_manifest(&manifest, &dir, None).unwrap();
+        let config = plan
+            .downloads
+            .iter()
+            .find(|d| d.category() == DownloadCategory::LogConfig)
+            .unwrap();
+        assert_eq!(
+            config.output_path(),
+            dir.join("assets/log_configs/client-1.12.xml")
+                .to_str()
+                .unwrap()
+        );
+        assert_eq!(config.total_size(), 888);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn plan_verifies_asset_index() {
+        let server = TestServer::start();
+        let (downloader, mut manifest) = legacy_plan_setup(&server);
+        manifest.asset_index.sha1 = "0".repeat(40);
+        let dir = temp_dir("plan-index");
+
+        assert!(matches!(
+            downloader.plan_by_manifest(&manifest, &dir, None),
+            Err(ClientDownloaderError::Download(_))
+        ));
+        assert!(!dir.join("assets/indexes/1.12.json").exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn java_platform_names() {
+        let name = |os, arch| java_platform(&Platform::new(os, arch));
+
+        assert_eq!(name("osx", "arm64"), Some(("macos", "aarch64")));
+        assert_eq!(name("windows", "x86_64"), Some(("windows", "x64")));
+        assert_eq!(name("linux", "arm64"), Some(("linux", "aarch64")));
+        assert_eq!(name("linux", "x86"), None);
+        assert_eq!(name("linux", "riscv64"), None);
+        assert_eq!(name("freebsd", "x86_64"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn java_runtime_from_local_endpoints() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let server = TestServer::start();
+        let java = b"#!/bin/sh\n".to_vec();
+        let libjli = b"libjli".to_vec();
+        let java_runtimes = serve_java_runtime(
+            &server,
+            &[("bin/java", &java, true), ("lib/libjli.so", &libjli, false)],
+            &[
+                r#""bin": {"type": "directory"}"#,
+                r#""legal": {"type": "directory"}"#,
+                r#""lib/libjli.so.1": {"type": "link", "target": "libjli.so"}"#,
+            ],
+        );
+
+        let mut downloader = ClientDownloader::with_manifest(
+            Endpoints {
+                java_runtimes,
+                ..Default::default()
+            },
+            Default::default(),
+        );
+        downloader.platform = Platform::new("linux", "x86_64");
+
+        let dir = temp_dir("java-runtime");
+        let results = downloader
+            .download_java_runtime(&dir, "java-runtime-gamma", None)
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| r.is_ok()));
+
+        let runtime = dir.join("java-runtime-gamma");
+        assert!(runtime.join("legal").is_dir());
+        let mode = std::fs::metadata(runtime.join("bin/java"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o111, 0o111);
+        assert_eq!(
+            std::fs::read_link(runtime.join("lib/libjli.so.1")).unwrap(),
+            Path::new("libjli.so")
+        );
+        assert_eq!(
+            std::fs::read(runtime.join("lib/libjli.so.1")).unwrap(),
+            libjli
+        );
+
+        assert!(matches!(
+            downloader.download_java_runtime(&dir, "jre-legacy", None),
+            Err(ClientDownloaderError::NoSuchRuntime(_))
+        ));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn java_archive_is_verified_and_extracted_once() {
+        use sha2::Digest;
+
+        let server = TestServer::start();
+        let url = server.url().to_string();
+        let archive = tar_gz("jdk-17.0.8");
+        let path = "/java/17.0.8/archive/jdk-17.0.8_linux-x64_bin.tar.gz";
+        let sha256 = format!("{:x}", sha2::Sha256::digest(&archive));
+        server.serve(path, &archive);
+        server.serve(&format!("{path}.sha256"), sha256.as_bytes());
+        let tampered = "/java/17.0.9/archive/jdk-17.0.9_linux-x64_bin.tar.gz";
+        server.serve(tampered, &archive);
+        server.serve(&format!("{tampered}.sha256"), "00".repeat(32).as_bytes());
+        let unsigned = "/java/17.0.10/archive/jdk-17.0.10_linux-x64_bin.tar.gz";
+        server.serve(unsigned, &archive);
+        server.serve(&format!("{unsigned}.sha256"), b"<html>Moved</html>");
+
+        let mut downloader = ClientDownloader::with_manifest(
+            Endpoints {
+                java: format!("{url}/java"),
+                ..Default::default()
+            },
+            Default::default(),
+        );
+        downloader.platform = Platform::new("linux", "x86_64");
+
+        let dir = temp_dir("java-archive-download");
+        let root = dir.to_str().unwrap();
+        assert!(!downloader.check_version(root, "17.0.8"));
+        downloader.download_java(root, "17.0.8", None).unwrap();
+
+        assert!(downloader.check_version(root, "17.0.8"));
+        assert!(dir.join("17.0.8/bin/java").is_file());
+        assert!(!dir.join("jdk-17.0.8.tar.gz").exists());
+        let marker = JavaMarker::read(&dir.join("17.0.8")).unwrap();
+        assert_eq!(marker.url, format!("{url}{path}"));
+        assert_eq!(marker.sha256, sha256);
+
+        downloader.download_java(root, "17.0.8", None).unwrap();
+        assert_eq!(server.requests(path).len(), 1);
+
+        assert!(matches!(
+            downloader.download_java(root, "17.0.9", None),
+            Err(JavaDownloadError::ChecksumMismatch { .. })
+        ));
+        assert!(!dir.join("jdk-17.0.9.tar.gz").exists());
+        assert!(!downloader.check_version(root, "17.0.9"));
+        // Without a checksum, the archive isn't even requested.
+        assert!(matches!(
+            downloader.download_java(root, "17.0.10", None),
+            Err(JavaDownloadError::MissingChecksum(_))
+        ));
+        assert!(server.requests(unsigned).is_empty());
+        assert!(!dir.join("jdk-17.0.10.tar.gz.sha256").exists());
+        assert!(matches!(
+            downloader.download_java(root, "99", None),
+            Err(JavaDownloadError::NotFound { version, .. }) if version == "99"
+        ));
+
+        downloader.platform = Platform::new("linux", "riscv64");
+        assert!(matches!(
+            downloader.download_java(root, "21", None),
+            Err(JavaDownloadError::UnsupportedPlatform { .. })
+        ));
+
+        // The checksum goes through the download service like the archive.
+        let checksum = &server.requests(&format!("{path}.sha256"))[0];
+        assert!(checksum.headers["user-agent"].starts_with("mc_downloader/"));
+        assert!(!dir.join("jdk-17.0.8.tar.gz.sha256").exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
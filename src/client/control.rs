@@ -0,0 +1,109 @@
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum State {
+    Running,
+    Paused,
+    Cancelled,
+}
+
+/// A handle to pause, resume or cancel a running download job from another thread or task.
+///
+/// Clones share the same state. Once cancelled a control stays cancelled, so use a fresh one
+/// for the next job.
+#[derive(Clone, Debug)]
+pub struct DownloadControl {
+    state: Arc<watch::Sender<State>>,
+}
+
+impl Default for DownloadControl {
+    fn default() -> Self {
+        Self {
+            state: Arc::new(watch::channel(State::Running).0),
+        }
+    }
+}
+
+impl DownloadControl {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Stops scheduling new downloads and aborts the ones in flight, removing their partial
+    /// files.
+    pub fn cancel(&self) {
+        self.state.send_replace(State::Cancelled);
+    }
+
+    /// Holds every download at its next chunk until [`Self::resume`] is called.
+    pub fn pause(&self) {
+        self.state
+            .send_if_modified(|state| replace_if(state, State::Running, State::Paused));
+    }
+
+    pub fn resume(&self) {
+        self.state
+            .send_if_modified(|state| replace_if(state, State::Paused, State::Running));
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.state.borrow() == State::Cancelled
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.state.borrow() == State::Paused
+    }
+
+    /// Resolves once the job is cancelled.
+    pub(crate) async fn cancelled(&self) {
+        let mut receiver = self.state.subscribe();
+        while *receiver.borrow_and_update() != State::Cancelled {
+            if receiver.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Waits while the job is paused; returns `false` if it was cancelled instead.
+    pub(crate) async fn proceed(&self) -> bool {
+        let mut receiver = self.state.subscribe();
+        loop {
+            match *receiver.borrow_and_update() {
+                State::Running => return true,
+                State::Cancelled => return false,
+                State::Paused => {}
+            }
+            if receiver.changed().await.is_err() {
+                return false;
+            }
+        }
+    }
+}
+
+fn replace_if(state: &mut State, from: State, to: State) -> bool {
+    if *state == from {
+        *state = to;
+        true
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DownloadControl;
+
+    #[test]
+    fn cancel_is_final() {
+        let control = DownloadControl::new();
+        control.pause();
+        assert!(control.is_paused());
+
+        control.cancel();
+        control.resume();
+        assert!(control.is_cancelled());
+        assert!(!control.is_paused());
+    }
+}
@@ -1,15 +1,52 @@
 use futures::stream::{self, StreamExt};
 use reqwest::Client;
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::fs::create_dir_all;
-use std::io::{Seek, SeekFrom, Write};
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::task::JoinError;
 
 use crate::error::DownloadError;
 use crate::manifest::ManifestFile;
 
-use super::{verify, DownloadOutput, DownloadResult, Progress, VerifyStatus};
+use super::events::Events;
+use super::retry::{parse_retry_after, RetryBudget};
+use super::skip::{Existing, VerifyCache};
+use super::verify::StreamVerifier;
+use super::{
+    DownloadControl, DownloadListener, DownloadOutput, DownloadResult, Mirrors, Progress,
+    RetryPolicy, SkipPolicy, VerifyStatus,
+};
+
+/// What a download is part of, so progress can be grouped.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum DownloadCategory {
+    Client,
+    AssetIndex,
+    Asset,
+    Library,
+    /// A jar of native libraries, extracted after download.
+    Native,
+    /// The log4j2 configuration of the client.
+    LogConfig,
+    Java,
+    #[default]
+    Other,
+}
+
+/// How the body served for a download is encoded.
+///
+/// The checksum and size of a download are always those of the decoded file.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Encoding {
+    #[default]
+    Identity,
+    /// A `.lzma` stream, decompressed as it arrives. Such downloads can't be resumed.
+    Lzma,
+}
 
 #[derive(Clone, Debug)]
 pub struct DownloadData {
@@ -18,6 +55,8 @@ pub struct DownloadData {
     pub(crate) output_path: String,
     pub(crate) sha1: String,
     pub(crate) total_size: u64,
+    pub(crate) category: DownloadCategory,
+    pub(crate) encoding: Encoding,
 }
 
 #[derive(Clone)]
@@ -25,119 +64,492 @@ pub struct DownloaderService {
     client: Client,
     downloads: Vec<DownloadData>,
     parallel_requests: u16,
-    retries: u16,
+    retry_policy: RetryPolicy,
     download_folder: PathBuf,
+    mirrors: Mirrors,
+    control: DownloadControl,
+    listener: Option<Arc<dyn DownloadListener>>,
+    skip_policy: SkipPolicy,
 }
 
 fn file_name_from_url(url: &str) -> std::path::PathBuf {
     if url.is_empty() {
         return std::path::PathBuf::new();
     }
-    let Ok(url) = reqwest::Url::parse(url) else { return std::path::PathBuf::new() };
+    let Ok(url) = reqwest::Url::parse(url) else {
+        return std::path::PathBuf::new();
+    };
 
     url.path_segments()
-        .map_or_else(std::path::PathBuf::new, |f| {
-            std::path::PathBuf::from(f.last().unwrap_or(""))
+        .map_or_else(std::path::PathBuf::new, |mut f| {
+            std::path::PathBuf::from(f.next_back().unwrap_or(""))
         })
 }
 
+/// Suffix appended to the output path while a file is being downloaded.
+const PART_EXTENSION: &str = "part";
+
+/// Outcome of a single HTTP transfer into a `.part` file.
+enum Transfer {
+    /// The whole body was received; contains the HTTP status and, if the download has a
+    /// checksum, the hash of everything in the `.part` file.
+    Complete(u16, Option<StreamVerifier>),
+    /// The server answered with an error status, and maybe a `Retry-After` delay.
+    Rejected(u16, Option<Duration>),
+    /// The connection dropped mid-body; the `.part` file keeps what was received.
+    Interrupted(u16),
+    /// The body could not be decoded, or decoded to the wrong size.
+    Corrupt(u16),
+    /// No response could be obtained at all.
+    Failed(reqwest::Error),
+    /// The job was cancelled while the transfer was running.
+    Cancelled,
+}
+
+pub(crate) fn part_path(output_path: &Path) -> PathBuf {
+    let mut name = output_path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(PART_EXTENSION);
+    output_path.with_file_name(name)
+}
+
+fn accepts_ranges(response: &reqwest::Response) -> bool {
+    response
+        .headers()
+        .get(reqwest::header::ACCEPT_RANGES)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.eq_ignore_ascii_case("bytes"))
+}
+
+/// Turns the body of a response into the content of the file.
+enum Decoder {
+    Identity,
+    Lzma(Box<lzma_rs::decompress::Stream<Vec<u8>>>),
+}
+
+impl Decoder {
+    fn new(encoding: Encoding) -> Self {
+        match encoding {
+            Encoding::Identity => Self::Identity,
+            Encoding::Lzma => Self::Lzma(Box::new(lzma_rs::decompress::Stream::new(Vec::new()))),
+        }
+    }
+
+    /// The content `bytes` decode to so far, or `None` if the body is corrupt.
+    fn decode<'a>(&mut self, bytes: &'a [u8]) -> Option<Cow<'a, [u8]>> {
+        match self {
+            Self::Identity => Some(Cow::Borrowed(bytes)),
+            Self::Lzma(stream) => {
+                stream.write_all(bytes).ok()?;
+                Some(Cow::Owned(std::mem::take(stream.get_output_mut()?)))
+            }
+        }
+    }
+
+    /// The rest of the content once the body is complete, or `None` if it ends early.
+    fn finish(self) -> Option<Vec<u8>> {
+        match self {
+            Self::Identity => Some(Vec::new()),
+            Self::Lzma(stream) => stream.finish().ok(),
+        }
+    }
+}
+
+/// The `.part` file being written, hashed on the way.
+struct Sink {
+    writer: std::io::BufWriter<std::fs::File>,
+    verifier: Option<StreamVerifier>,
+    written: u64,
+}
+
+impl Sink {
+    fn write(&mut self, data: &[u8]) -> Result<(), DownloadError> {
+        self.writer
+            .write_all(data)
+            .map_err(|e| DownloadError::Setup(e.to_string()))?;
+        if let Some(verifier) = &mut self.verifier {
+            verifier.update(data);
+        }
+        self.written += data.len() as u64;
+        Ok(())
+    }
+}
+
+/// Progress of a single file, across retries and mirrors.
+struct FileProgress<'a> {
+    download: &'a Arc<DownloadData>,
+    events: &'a Events,
+    /// Bytes of this file already reported, so a refetch from zero does not count the same
+    /// bytes twice.
+    reported: u64,
+}
+
+impl FileProgress<'_> {
+    fn report(&mut self, current: u64) {
+        if current > self.reported {
+            self.events
+                .progress(self.download, current - self.reported, current);
+            self.reported = current;
+        }
+    }
+}
+
+/// Streams `url` into `part_path`, resuming from the current length of the part file when
+/// `resume` is set and the server answers the `Range` request with `206 Partial Content`.
+///
+/// The body is decoded and hashed as it arrives; only the already present part of a resumed
+/// file is read back.
 async fn download_url(
-    client: reqwest::Client,
-    url: String,
-    writer: &mut std::io::BufWriter<std::fs::File>,
-    progress_opt: Option<Progress>,
-) -> u16 {
-    let Some(progress) = progress_opt else { return reqwest::StatusCode::NOT_IMPLEMENTED.as_u16() };
-    if let Ok(mut response) = client.get(&url).send().await {
-        let mut current: u64 = 0;
-        writer.seek(SeekFrom::Start(current)).unwrap_or(0);
-
-        while let Some(bytes) = response.chunk().await.unwrap_or(None) {
-            if writer.write_all(&bytes).is_err() {}
-
-            current += bytes.len() as u64;
-            progress.lock().unwrap().progress(bytes.len() as u64);
+    client: &Client,
+    url: &str,
+    download: &DownloadData,
+    part_path: &Path,
+    resume: &mut bool,
+    progress: &mut FileProgress<'_>,
+    control: &DownloadControl,
+) -> Result<Transfer, DownloadError> {
+    let identity = download.encoding == Encoding::Identity;
+    let offset = if *resume && identity {
+        part_path.metadata().map(|m| m.len()).unwrap_or(0)
+    } else {
+        0
+    };
+
+    let mut request = client.get(url);
+    if offset > 0 {
+        request = request.header(reqwest::header::RANGE, format!("bytes={offset}-"));
+    }
+
+    let response = tokio::select! {
+        response = request.send() => response,
+        _ = control.cancelled() => return Ok(Transfer::Cancelled),
+    };
+    let mut response = match response {
+        Ok(response) => response,
+        Err(e) => return Ok(Transfer::Failed(e)),
+    };
+    let status = response.status();
+
+    let start_verifier = |len: u64| {
+        if download.sha1.is_empty() {
+            return Ok(None);
+        }
+        StreamVerifier::resume_from(part_path, len)
+            .map(Some)
+            .map_err(|e| DownloadError::Setup(e.to_string()))
+    };
+
+    // The part file already holds the whole body, or is bogus and has to be refetched.
+    if status == reqwest::StatusCode::RANGE_NOT_SATISFIABLE && offset > 0 {
+        if download.total_size == 0 || offset == download.total_size {
+            return Ok(Transfer::Complete(
+                reqwest::StatusCode::OK.as_u16(),
+                start_verifier(offset)?,
+            ));
         }
+        std::fs::remove_file(part_path).map_err(|e| DownloadError::Setup(e.to_string()))?;
+        return Ok(Transfer::Interrupted(status.as_u16()));
+    }
+
+    if !status.is_success() {
+        let retry_after = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_retry_after);
+        return Ok(Transfer::Rejected(status.as_u16(), retry_after));
+    }
+
+    let partial = status == reqwest::StatusCode::PARTIAL_CONTENT && offset > 0;
+    *resume = identity && (accepts_ranges(&response) || partial);
 
-        response.status().as_u16()
+    let file = if partial {
+        std::fs::OpenOptions::new().append(true).open(part_path)
     } else {
-        reqwest::StatusCode::BAD_REQUEST.as_u16()
+        std::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(part_path)
     }
+    .map_err(|e| DownloadError::Setup(e.to_string()))?;
+    let current = if partial { offset } else { 0 };
+    let mut sink = Sink {
+        writer: std::io::BufWriter::new(file),
+        verifier: start_verifier(current)?,
+        written: current,
+    };
+    let mut decoder = Decoder::new(download.encoding);
+    progress.report(current);
+
+    loop {
+        if !control.proceed().await {
+            return Ok(Transfer::Cancelled);
+        }
+        let chunk = tokio::select! {
+            chunk = response.chunk() => chunk,
+            _ = control.cancelled() => return Ok(Transfer::Cancelled),
+        };
+        match chunk {
+            Ok(Some(bytes)) => {
+                let Some(content) = decoder.decode(&bytes) else {
+                    return Ok(Transfer::Corrupt(status.as_u16()));
+                };
+                sink.write(&content)?;
+                progress.report(sink.written);
+            }
+            Ok(None) => break,
+            Err(_) => {
+                sink.writer
+                    .flush()
+                    .map_err(|e| DownloadError::Setup(e.to_string()))?;
+                return Ok(Transfer::Interrupted(status.as_u16()));
+            }
+        }
+    }
+
+    let Some(rest) = decoder.finish() else {
+        return Ok(Transfer::Corrupt(status.as_u16()));
+    };
+    sink.write(&rest)?;
+    progress.report(sink.written);
+    if !identity && download.total_size > 0 && sink.written != download.total_size {
+        return Ok(Transfer::Corrupt(status.as_u16()));
+    }
+
+    sink.writer
+        .into_inner()
+        .map_err(|e| DownloadError::Setup(e.to_string()))?
+        .sync_all()
+        .map_err(|e| DownloadError::Setup(e.to_string()))?;
+
+    Ok(Transfer::Complete(status.as_u16(), sink.verifier))
+}
+
+/// State shared by every download of a single run.
+struct Run<'a> {
+    service: &'a DownloaderService,
+    budget: RetryBudget,
+    cache: VerifyCache,
+    events: Events,
 }
 
 async fn download(
-    client: Client,
-    download: DownloadData,
-    retries: u16,
-    download_folder: PathBuf,
-    progress: Option<Progress>,
+    run: &Run<'_>,
+    download: Arc<DownloadData>,
 ) -> Result<DownloadOutput, DownloadError> {
-    let mut download_successful = false;
-    let mut output_path = download_folder.clone();
-    output_path.push(download.output_path);
+    download_file(run, &download)
+        .await
+        .inspect_err(|e| run.events.failed(&download, e))
+}
+
+async fn download_file(
+    run: &Run<'_>,
+    download: &Arc<DownloadData>,
+) -> Result<DownloadOutput, DownloadError> {
+    let service = run.service;
+    let events = &run.events;
+    let mut output_path = service.download_folder.clone();
+    output_path.push(&download.output_path);
 
     let mut result = DownloadOutput {
         status: reqwest::StatusCode::OK.as_u16(),
         // @TODO
         file_name: download.file_name.clone(),
         file_path: output_path.clone(),
+        url: String::new(),
         verified: VerifyStatus::NotVerified,
+        repaired: false,
     };
 
-    if output_path.exists() && output_path.is_file() {
-        if let Ok(metadata) = output_path.metadata() {
-            if metadata.len() == download.total_size {
-                return Ok(result);
-            }
+    // Downloads not started yet when the job is cancelled are never scheduled.
+    if !service.control.proceed().await {
+        return Err(DownloadError::Cancelled(result));
+    }
+    events.started(download);
+
+    match service
+        .skip_policy
+        .check(&output_path, download, &run.cache)
+        .await
+    {
+        Existing::Valid(verified) => {
+            result.verified = verified;
+            events.skipped(download);
+            return Ok(result);
+        }
+        Existing::Corrupted => {
+            events.verified(download, &VerifyStatus::Failed);
+            result.repaired = true;
         }
+        Existing::Missing => {}
     }
 
     create_dir_all(output_path.parent().unwrap())
         .map_err(|e| DownloadError::Setup(e.to_string()))?;
 
-    if let Ok(file) = std::fs::OpenOptions::new()
-        .create(true)
-        .write(true)
-        .open(output_path)
-    {
-        let mut writer = std::io::BufWriter::new(file);
+    let part_path = part_path(&output_path);
+    let mut progress = FileProgress {
+        download,
+        events,
+        reported: 0,
+    };
+    let mut failure = None;
+
+    for url in service.mirrors.candidates(&download.url) {
+        result.url = url;
+        result.verified = VerifyStatus::NotVerified;
+
+        match fetch(run, download, &part_path, &mut progress, &mut result).await {
+            Ok(()) => {
+                std::fs::rename(&part_path, &output_path).map_err(|_| {
+                    discard(&part_path);
+                    DownloadError::File(result.clone())
+                })?;
+                sync_parent(&output_path).map_err(|_| DownloadError::Persist(result.clone()))?;
+                run.cache.insert(&output_path, &download.sha1);
+                events.finished(download, &result);
+                return Ok(result);
+            }
+            Err(e @ (DownloadError::Setup(_) | DownloadError::Cancelled(_))) => return Err(e),
+            Err(e) => failure = Some(e),
+        }
+    }
+
+    Err(failure.unwrap_or(DownloadError::Download(result)))
+}
+
+/// Fetches `result.url` into `part_path` and verifies it, retrying and resuming as needed.
+async fn fetch(
+    run: &Run<'_>,
+    download: &Arc<DownloadData>,
+    part_path: &Path,
+    progress: &mut FileProgress<'_>,
+    result: &mut DownloadOutput,
+) -> Result<(), DownloadError> {
+    let service = run.service;
+    let policy = &service.retry_policy;
+    let mut resume = true;
+    // A resumed file that fails verification is refetched from scratch once.
+    let mut refetched = false;
 
-        let url = download.url;
-        for _ in 1..=retries {
-            let s = reqwest::StatusCode::from_u16(
-                download_url(client.clone(), url.clone(), &mut writer, progress.clone()).await,
+    loop {
+        let mut completed = None;
+        let mut resumed = false;
+        let mut interrupted;
+        let mut attempt = 0;
+
+        loop {
+            attempt += 1;
+            resumed |= resume && part_path.exists();
+            let transfer = download_url(
+                &service.client,
+                &result.url,
+                download,
+                part_path,
+                &mut resume,
+                progress,
+                &service.control,
             )
-            .unwrap_or(reqwest::StatusCode::BAD_REQUEST);
+            .await
+            .inspect_err(|_| discard(part_path))?;
 
-            result.status = s.as_u16();
+            interrupted = matches!(transfer, Transfer::Interrupted(_));
+            let (retryable, retry_after) = match transfer {
+                Transfer::Complete(status, verifier) => {
+                    result.status = status;
+                    completed = Some(match verifier {
+                        Some(verifier) => verifier.verify(&download.sha1),
+                        None => VerifyStatus::Ok,
+                    });
+                    break;
+                }
+                Transfer::Corrupt(status) => {
+                    result.status = status;
+                    completed = Some(VerifyStatus::Failed);
+                    break;
+                }
+                Transfer::Rejected(status, retry_after) => {
+                    result.status = status;
+                    (policy.is_retryable_status(status), retry_after)
+                }
+                Transfer::Interrupted(status) => {
+                    result.status = status;
+                    (true, None)
+                }
+                Transfer::Failed(e) => {
+                    result.status = e
+                        .status()
+                        .unwrap_or(reqwest::StatusCode::BAD_REQUEST)
+                        .as_u16();
+                    (policy.is_retryable_error(&e), None)
+                }
+                Transfer::Cancelled => {
+                    discard(part_path);
+                    return Err(DownloadError::Cancelled(result.clone()));
+                }
+            };
 
-            if s.is_server_error() {
+            if !retryable || attempt >= policy.max_attempts() || !run.budget.try_spend() {
                 break;
             }
+            let delay = policy.delay(attempt, retry_after);
+            run.events.retrying(download, &result.url, attempt, delay);
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = service.control.cancelled() => {
+                    discard(part_path);
+                    return Err(DownloadError::Cancelled(result.clone()));
+                }
+            }
+        }
 
-            if s.is_success() {
-                download_successful = true;
-                break;
+        let Some(verified) = completed else {
+            // Only a transfer cut off by a server that can resume it leaves something worth
+            // keeping for the next attempt.
+            if !(interrupted && resume) {
+                discard(part_path);
             }
+            return Err(DownloadError::Download(result.clone()));
+        };
+
+        result.verified = verified;
+        run.events.verified(download, &result.verified);
+
+        if result.verified == VerifyStatus::Failed && resumed && !refetched {
+            refetched = true;
+            resume = false;
+            continue;
         }
+
+        break;
     }
 
-    if !download_successful {
-        return Err(DownloadError::Download(result));
+    if result.verified == VerifyStatus::Failed {
+        discard(part_path);
+        return Err(DownloadError::Verification(result.clone()));
     }
 
-    result.verified = if !download.sha1.is_empty() {
-        verify::verify_file(download.sha1.as_str(), result.file_path.clone())
-    } else {
-        VerifyStatus::Ok
-    };
+    Ok(())
+}
 
-    // Ignoring verification
-    if result.verified == VerifyStatus::Failed {
-        return Err(DownloadError::Verification(result));
+/// Removes a temporary file left by a failed download.
+fn discard(part_path: &Path) {
+    if part_path.exists() {
+        let _ = std::fs::remove_file(part_path);
+    }
+}
+
+/// Persists the rename of a finished file into `output_path`.
+///
+/// Directories can't be opened for syncing on Windows.
+fn sync_parent(output_path: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    if let Some(parent) = output_path.parent() {
+        std::fs::File::open(parent)?.sync_all()?;
     }
 
-    Ok(result)
+    Ok(())
 }
 
 impl DownloadData {
@@ -153,8 +565,39 @@ impl DownloadData {
             output_path: path.to_string(),
             sha1: String::new(),
             total_size: 0,
+            category: DownloadCategory::Other,
+            encoding: Encoding::Identity,
         }
     }
+
+    pub fn with_category(mut self, category: DownloadCategory) -> Self {
+        self.category = category;
+        self
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn file_name(&self) -> &str {
+        &self.file_name
+    }
+
+    pub fn output_path(&self) -> &str {
+        &self.output_path
+    }
+
+    pub fn total_size(&self) -> u64 {
+        self.total_size
+    }
+
+    pub fn category(&self) -> DownloadCategory {
+        self.category
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
 }
 
 impl From<ManifestFile> for DownloadData {
@@ -170,6 +613,8 @@ impl From<ManifestFile> for DownloadData {
             output_path: manifest.path.unwrap_or_default(),
             sha1: manifest.sha1,
             total_size: manifest.size,
+            category: DownloadCategory::Other,
+            encoding: Encoding::Identity,
         }
     }
 }
@@ -189,8 +634,12 @@ impl Default for DownloaderService {
                 .unwrap(),
             downloads: Vec::new(),
             parallel_requests: 32,
-            retries: 3,
+            retry_policy: RetryPolicy::default(),
             download_folder: Default::default(),
+            mirrors: Default::default(),
+            control: Default::default(),
+            listener: None,
+            skip_policy: SkipPolicy::default(),
         }
     }
 }
@@ -198,7 +647,7 @@ impl Default for DownloaderService {
 impl DownloaderService {
     pub fn new(download_folder: PathBuf) -> Self {
         Self {
-            download_folder: download_folder,
+            download_folder,
             ..Default::default()
         }
     }
@@ -218,8 +667,33 @@ impl DownloaderService {
         self
     }
 
-    pub fn with_retries(&mut self, retries: u16) -> &mut Self {
-        self.retries = retries;
+    /// Sets how failed requests are retried, see [`RetryPolicy`].
+    pub fn with_retry_policy(&mut self, retry_policy: RetryPolicy) -> &mut Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Sets the fallback hosts tried when a download fails or doesn't verify.
+    pub fn with_mirrors(&mut self, mirrors: Mirrors) -> &mut Self {
+        self.mirrors = mirrors;
+        self
+    }
+
+    /// Sets the handle used to pause, resume or cancel [`Self::run`] and [`Self::run_async`].
+    pub fn with_control(&mut self, control: DownloadControl) -> &mut Self {
+        self.control = control;
+        self
+    }
+
+    /// Sets the receiver of per-file [`DownloadEvent`](super::DownloadEvent)s.
+    pub fn with_listener(&mut self, listener: Arc<dyn DownloadListener>) -> &mut Self {
+        self.listener = Some(listener);
+        self
+    }
+
+    /// Sets how files already present are checked, see [`SkipPolicy`].
+    pub fn with_skip_policy(&mut self, skip_policy: SkipPolicy) -> &mut Self {
+        self.skip_policy = skip_policy;
         self
     }
 
@@ -228,51 +702,357 @@ impl DownloaderService {
         self
     }
 
+    /// Runs every download on a runtime owned by this call and blocks until they finish.
+    ///
+    /// This must not be called from within an async runtime; use [`Self::run_async`] there.
     pub fn run(&self, progress: Option<Progress>) -> Result<Vec<DownloadResult>, JoinError> {
         let rt = tokio::runtime::Runtime::new().unwrap();
-        let cl = self.client.clone();
-        let max = self
+        let service = self.clone();
+
+        rt.block_on(rt.spawn(async move { service.run_async(progress).await }))
+    }
+
+    /// Runs every download on the caller's executor.
+    ///
+    /// Downloads sharing an output path, like asset names pointing at the same object, are
+    /// fetched once and all get that result.
+    pub async fn run_async(&self, progress: Option<Progress>) -> Vec<DownloadResult> {
+        let mut unique = Vec::new();
+        let mut slots = HashMap::new();
+        let slot_of = self
             .downloads
-            .clone()
             .iter()
-            .map(|d| d.total_size)
-            .reduce(|accum, i| accum + i)
-            .unwrap_or_default();
-
-        let download_folder = self.download_folder.clone();
-        let downloads = self.downloads.clone();
-        let retries = self.retries;
-        let parallel_requests = self.parallel_requests;
-        let progress = progress.clone();
-
-        if progress.is_some() {
-            progress.as_ref().unwrap().lock().unwrap().setup(max);
+            .map(|d| {
+                *slots.entry(d.output_path.as_str()).or_insert_with(|| {
+                    unique.push(d.clone());
+                    unique.len() - 1
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let run = Run {
+            service: self,
+            budget: self.retry_policy.budget(),
+            cache: VerifyCache::load(&self.skip_policy),
+            events: Events::new(self.listener.clone(), progress, &unique),
+        };
+
+        let res = stream::iter(unique.into_iter().map(Arc::new))
+            .map(|d| download(&run, d))
+            .buffered(self.parallel_requests as usize)
+            .collect::<Vec<DownloadResult>>()
+            .await;
+
+        // The cache only saves hashing work; losing it is harmless.
+        let _ = run.cache.save();
+        run.events.done();
+
+        self.downloads
+            .iter()
+            .zip(slot_of)
+            .map(|(d, slot)| {
+                res[slot].clone().map(|mut output| {
+                    output.file_name = d.file_name.clone();
+                    output
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
+
+    use crate::client::test_server::{sha1, temp_dir, Response, TestServer};
+
+    use crate::client::VerifyStatus;
+    use crate::error::DownloadError;
+
+    use super::{
+        part_path, DownloadControl, DownloadData, DownloaderService, Encoding, Mirrors, RetryPolicy,
+    };
+
+    fn fast_retries() -> RetryPolicy {
+        let mut policy = RetryPolicy::default();
+        policy.with_base_delay(Duration::from_millis(1));
+        policy
+    }
+
+    #[test]
+    fn interrupted_download_resumes_with_range() {
+        let server = TestServer::start();
+        let body = (0..64 * 1024).map(|i| (i % 251) as u8).collect::<Vec<u8>>();
+        let calls = AtomicUsize::new(0);
+        let served = body.clone();
+        server.route("/file.bin", move |request| {
+            let mut response = Response::ranged(request, &served);
+            if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                response.cut_after = Some(served.len() / 2);
+            }
+            response
+        });
+
+        let dir = temp_dir("resume");
+        let mut download = DownloadData::new(&format!("{}/file.bin", server.url()), "file.bin");
+        download.total_size = body.len() as u64;
+        download.sha1 = sha1(&body);
+        let results = DownloaderService::new(dir.clone())
+            .with_downloads(vec![download])
+            .with_retry_policy(fast_retries())
+            .run(None)
+            .unwrap();
+
+        assert_eq!(results[0].as_ref().unwrap().verified, VerifyStatus::Ok);
+        assert_eq!(std::fs::read(dir.join("file.bin")).unwrap(), body);
+        assert!(!part_path(&dir.join("file.bin")).exists());
+
+        let requests = server.requests("/file.bin");
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].headers["range"].starts_with("bytes="));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn failed_downloads_leave_existing_file_untouched() {
+        let server = TestServer::start();
+        server.serve("/corrupt.bin", b"corrupt");
+        server.route("/missing.bin", |_| Response::status(404));
+
+        let dir = temp_dir("untouched");
+        let downloads = ["corrupt.bin", "missing.bin"].map(|name| {
+            std::fs::write(dir.join(name), b"old").unwrap();
+            let mut download = DownloadData::new(&format!("{}/{name}", server.url()), name);
+            // Matches neither the existing file nor what the server sends.
+            download.sha1 = "0".repeat(40);
+            download
+        });
+        let results = DownloaderService::new(dir.clone())
+            .with_downloads(downloads.into())
+            .run(None)
+            .unwrap();
+
+        assert!(matches!(results[0], Err(DownloadError::Verification(_))));
+        assert!(matches!(results[1], Err(DownloadError::Download(_))));
+        for name in ["corrupt.bin", "missing.bin"] {
+            assert_eq!(std::fs::read(dir.join(name)).unwrap(), b"old");
+            assert!(!part_path(&dir.join(name)).exists());
         }
 
-        let result = rt.spawn(async move {
-            let progress = progress.clone();
-            let res = {
-                stream::iter(downloads)
-                    .map(|d| {
-                        download(
-                            cl.clone(),
-                            d,
-                            retries,
-                            download_folder.clone(),
-                            progress.clone(),
-                        )
-                    })
-                    .buffered(parallel_requests as usize)
-                    .collect::<Vec<DownloadResult>>()
-                    .await
-            };
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn run_async_inside_runtime() {
+        let server = TestServer::start();
+        server.serve("/file.bin", b"body");
+
+        let dir = temp_dir("run-async");
+        let download = DownloadData::new(&format!("{}/file.bin", server.url()), "file.bin");
+        let results = DownloaderService::new(dir.clone())
+            .with_downloads(vec![download])
+            .run_async(None)
+            .await;
+
+        assert!(results[0].is_ok());
+        assert_eq!(std::fs::read(dir.join("file.bin")).unwrap(), b"body");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn shared_output_path_is_fetched_once() {
+        let server = TestServer::start();
+        server.serve("/object", b"shared");
+
+        let dir = temp_dir("shared-output");
+        let downloads = ["a.json", "b.json", "c.json", "d.json"].map(|name| {
+            let mut download = DownloadData::new(&format!("{}/object", server.url()), name);
+            download.file_name = name.to_string();
+            download.output_path = "objects/ab/same".to_string();
+            download.sha1 = sha1(b"shared");
+            download
+        });
+        let results = DownloaderService::new(dir.clone())
+            .with_downloads(downloads.into())
+            .run(None)
+            .unwrap();
+
+        let names = results
+            .iter()
+            .map(|r| r.as_ref().unwrap().file_name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["a.json", "b.json", "c.json", "d.json"]);
+        assert_eq!(
+            std::fs::read(dir.join("objects/ab/same")).unwrap(),
+            b"shared"
+        );
+        assert_eq!(server.requests("/object").len(), 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn part_path_is_sidecar() {
+        let path = PathBuf::from("versions/1.19.4/1.19.4.jar");
+
+        assert_eq!(
+            part_path(&path),
+            PathBuf::from("versions/1.19.4/1.19.4.jar.part")
+        );
+    }
+
+    #[test]
+    fn failing_mirrors_fall_through_to_next() {
+        let server = TestServer::start();
+        let body = b"verified content".to_vec();
+        server.route("/primary/file.bin", |_| Response::status(503));
+        server.serve("/broken/file.bin", b"corrupted content");
+        server.serve("/good/file.bin", &body);
+
+        let mut mirrors = Mirrors::new();
+        mirrors.with_host([
+            format!("{}/primary", server.url()),
+            format!("{}/broken", server.url()),
+            format!("{}/good", server.url()),
+        ]);
+
+        let dir = temp_dir("mirrors");
+        let mut download =
+            DownloadData::new(&format!("{}/primary/file.bin", server.url()), "file.bin");
+        download.sha1 = sha1(&body);
+        download.total_size = body.len() as u64;
 
-            if progress.is_some() {
-                progress.unwrap().lock().unwrap().done();
+        let results = DownloaderService::new(dir.clone())
+            .with_downloads(vec![download])
+            .with_retry_policy(fast_retries())
+            .with_mirrors(mirrors)
+            .run(None)
+            .unwrap();
+
+        let output = results[0].as_ref().unwrap();
+        assert_eq!(output.url, format!("{}/good/file.bin", server.url()));
+        assert_eq!(std::fs::read(dir.join("file.bin")).unwrap(), body);
+        assert_eq!(server.requests("/broken/file.bin").len(), 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn retry_after_is_honoured_and_client_errors_are_not_retried() {
+        let server = TestServer::start();
+        let calls = AtomicUsize::new(0);
+        server.route("/busy.bin", move |request| {
+            if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                let mut response = Response::status(429);
+                response
+                    .headers
+                    .push(("Retry-After".to_string(), "1".to_string()));
+                response
+            } else {
+                Response::ranged(request, b"done")
             }
-            res
         });
 
-        futures::executor::block_on(result)
+        let dir = temp_dir("retry");
+        let started = Instant::now();
+        let results = DownloaderService::new(dir.clone())
+            .with_downloads(vec![
+                DownloadData::new(&format!("{}/busy.bin", server.url()), "busy.bin"),
+                DownloadData::new(&format!("{}/missing.bin", server.url()), "missing.bin"),
+            ])
+            .with_retry_policy(fast_retries())
+            .run(None)
+            .unwrap();
+
+        assert!(results[0].is_ok());
+        assert!(started.elapsed() >= Duration::from_secs(1));
+        assert_eq!(server.requests("/busy.bin").len(), 2);
+
+        assert!(results[1].is_err());
+        assert_eq!(server.requests("/missing.bin").len(), 1);
+        assert!(!part_path(&dir.join("missing.bin")).exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn cancel_aborts_in_flight_and_pending_downloads() {
+        let server = TestServer::start();
+        server.serve("/fast.bin", b"fast");
+        server.route("/slow.bin", |_| {
+            let mut response = Response::ok(&[0; 1024]);
+            response.cut_after = Some(512);
+            std::thread::sleep(Duration::from_secs(5));
+            response
+        });
+
+        let dir = temp_dir("cancel");
+        let control = DownloadControl::new();
+        let canceller = control.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(300));
+            canceller.cancel();
+        });
+
+        let started = Instant::now();
+        let results = DownloaderService::new(dir.clone())
+            .with_downloads(vec![
+                DownloadData::new(&format!("{}/fast.bin", server.url()), "fast.bin"),
+                DownloadData::new(&format!("{}/slow.bin", server.url()), "slow.bin"),
+                DownloadData::new(&format!("{}/fast.bin", server.url()), "later.bin"),
+            ])
+            .with_parallel_requests(1)
+            .with_control(control)
+            .run(None)
+            .unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(4));
+        assert!(results[0].is_ok());
+        assert!(matches!(results[1], Err(DownloadError::Cancelled(_))));
+        assert!(matches!(results[2], Err(DownloadError::Cancelled(_))));
+        assert!(!part_path(&dir.join("slow.bin")).exists());
+        assert_eq!(server.requests("/fast.bin").len(), 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn lzma_bodies_are_decompressed_and_verified() {
+        let server = TestServer::start();
+        let body = (0..64 * 1024).map(|i| (i % 251) as u8).collect::<Vec<u8>>();
+        let mut compressed = Vec::new();
+        lzma_rs::lzma_compress(&mut body.as_slice(), &mut compressed).unwrap();
+        server.serve("/good.lzma", &compressed);
+        server.serve("/cut.lzma", &compressed[..compressed.len() / 2]);
+
+        let dir = temp_dir("lzma");
+        let download = |name: &str| {
+            let mut download = DownloadData::new(&format!("{}/{name}.lzma", server.url()), name);
+            download.sha1 = sha1(&body);
+            download.total_size = body.len() as u64;
+            download.encoding = Encoding::Lzma;
+            download
+        };
+        let results = DownloaderService::new(dir.clone())
+            .with_downloads(vec![download("good"), download("cut")])
+            .with_retry_policy(fast_retries())
+            .run(None)
+            .unwrap();
+
+        assert_eq!(results[0].as_ref().unwrap().verified, VerifyStatus::Ok);
+        assert_eq!(std::fs::read(dir.join("good")).unwrap(), body);
+        assert!(matches!(results[1], Err(DownloadError::Verification(_))));
+        assert!(!dir.join("cut").exists());
+        assert!(!part_path(&dir.join("cut")).exists());
+        assert!(server
+            .requests("/good.lzma")
+            .iter()
+            .all(|r| !r.headers.contains_key("range")));
+
+        std::fs::remove_dir_all(dir).unwrap();
     }
 }
@@ -0,0 +1,107 @@
+use super::mirrors::strip_base;
+
+/// Default location of the launcher version manifest.
+pub const VERSION_MANIFEST_URL: &str =
+    "https://launchermeta.mojang.com/mc/game/version_manifest.json";
+/// Default base URL for asset objects.
+pub const ASSETS_BASE_URL: &str = "https://resources.download.minecraft.net";
+/// Default base URL for libraries.
+pub const LIBRARIES_BASE_URL: &str = "https://libraries.minecraft.net";
+/// Default base URL for Java archives.
+pub const JAVA_BASE_URL: &str = "https://download.oracle.com/java";
+/// Default location of Mojang's Java runtime index.
+pub const JAVA_RUNTIMES_URL: &str = "https://launchermeta.mojang.com/v1/products/java-runtime/2ec0cc96c44e5a76b9c8b7c39df7210883d12871/all.json";
+
+/// The hosts the `client` module downloads from.
+///
+/// Point these at a mirror or a local server to avoid the official hosts.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Endpoints {
+    /// URL of the launcher version manifest.
+    pub version_manifest: String,
+    /// Base URL asset objects are fetched from as `<base>/<hash[..2]>/<hash>`.
+    pub assets: String,
+    /// Base URL replacing the official library host in library URLs.
+    pub libraries: String,
+    /// Base URL Java archives are fetched from.
+    pub java: String,
+    /// URL of Mojang's Java runtime index.
+    pub java_runtimes: String,
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Self {
+            version_manifest: VERSION_MANIFEST_URL.to_string(),
+            assets: ASSETS_BASE_URL.to_string(),
+            libraries: LIBRARIES_BASE_URL.to_string(),
+            java: JAVA_BASE_URL.to_string(),
+            java_runtimes: JAVA_RUNTIMES_URL.to_string(),
+        }
+    }
+}
+
+impl Endpoints {
+    /// URL of the asset object with the given hash.
+    pub fn asset_url(&self, hash: &str) -> String {
+        format!("{}/{}/{}", trim(&self.assets), &hash[..2], hash)
+    }
+
+    /// Rewrites a library URL from the official host to the configured one.
+    ///
+    /// URLs pointing anywhere else (e.g. a mod loader's maven) are left untouched.
+    pub fn library_url(&self, url: &str) -> String {
+        match strip_base(url, LIBRARIES_BASE_URL) {
+            Some(path) => format!("{}{}", trim(&self.libraries), path),
+            None => url.to_string(),
+        }
+    }
+
+    /// URL of the Java archive for a version and platform.
+    pub fn java_url(&self, version: &str, os: &str, arch: &str, ext: &str) -> String {
+        format!(
+            "{}/{version}/archive/jdk-{version}_{os}-{arch}_bin{ext}",
+            trim(&self.java)
+        )
+    }
+}
+
+fn trim(base: &str) -> &str {
+    base.trim_end_matches('/')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Endpoints;
+
+    #[test]
+    fn library_url_rewrites_official_host() {
+        let endpoints = Endpoints {
+            libraries: "http://mirror.local/maven/".to_string(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            endpoints.library_url("https://libraries.minecraft.net/com/mojang/brigadier.jar"),
+            "http://mirror.local/maven/com/mojang/brigadier.jar"
+        );
+        assert_eq!(
+            endpoints.library_url("https://maven.fabricmc.net/net/fabricmc/loader.jar"),
+            "https://maven.fabricmc.net/net/fabricmc/loader.jar"
+        );
+        assert_eq!(
+            endpoints.library_url("https://libraries.minecraft.net.evil/x.jar"),
+            "https://libraries.minecraft.net.evil/x.jar"
+        );
+    }
+
+    #[test]
+    fn asset_url_uses_hash_prefix() {
+        let endpoints = Endpoints::default();
+
+        assert_eq!(
+            endpoints.asset_url("bdf48ef6b5d0d23bbb02e17d04865216179f510a"),
+            "https://resources.download.minecraft.net/bd/bdf48ef6b5d0d23bbb02e17d04865216179f510a"
+        );
+    }
+}
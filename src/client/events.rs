@@ -0,0 +1,290 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::error::DownloadError;
+
+use super::{DownloadData, DownloadOutput, Progress, VerifyStatus};
+
+/// Aggregate progress of a download job.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DownloadStats {
+    /// Bytes of every file in the job.
+    pub total_bytes: u64,
+    /// Bytes already on disk, whether downloaded in this job or skipped as present.
+    pub completed_bytes: u64,
+    /// Bytes actually transferred by this job.
+    pub transferred_bytes: u64,
+    pub total_files: usize,
+    /// Files that finished, were skipped or failed.
+    pub done_files: usize,
+    /// Average transfer rate since the job started.
+    pub bytes_per_second: f64,
+    /// Estimated time left at the current rate, if anything was transferred yet.
+    pub eta: Option<Duration>,
+}
+
+/// Something that happened to a single file, or to the whole job.
+#[derive(Clone, Debug)]
+pub enum DownloadEvent {
+    /// The file was scheduled and is about to be checked or fetched.
+    Started(Arc<DownloadData>),
+    /// Bytes of the file were written.
+    Progress {
+        download: Arc<DownloadData>,
+        /// Bytes of this file received so far.
+        current: u64,
+        stats: DownloadStats,
+    },
+    /// A request for the file failed and will be tried again after `delay`.
+    Retrying {
+        download: Arc<DownloadData>,
+        url: String,
+        attempt: u16,
+        delay: Duration,
+    },
+    /// The fetched file went through verification.
+    Verified {
+        download: Arc<DownloadData>,
+        status: VerifyStatus,
+    },
+    /// The file was already present and was not fetched.
+    Skipped(Arc<DownloadData>),
+    /// The file could not be downloaded.
+    Failed {
+        download: Arc<DownloadData>,
+        error: DownloadError,
+    },
+    /// The file was downloaded and moved into place.
+    Finished {
+        download: Arc<DownloadData>,
+        output: DownloadOutput,
+    },
+    /// Every file of the job was handled.
+    Done(DownloadStats),
+}
+
+/// Receives [`DownloadEvent`]s as they happen.
+///
+/// Events are delivered from the download tasks themselves, so implementations should be
+/// quick and do their own synchronisation if they need any.
+pub trait DownloadListener: Send + Sync {
+    fn on_event(&self, event: &DownloadEvent);
+}
+
+/// Forwards every event into a channel, e.g. to consume them as a stream on a UI task.
+impl DownloadListener for UnboundedSender<DownloadEvent> {
+    fn on_event(&self, event: &DownloadEvent) {
+        let _ = self.send(event.clone());
+    }
+}
+
+/// Dispatches the events of one job to its listener and legacy progress reporter.
+pub(crate) struct Events {
+    listener: Option<Arc<dyn DownloadListener>>,
+    progress: Option<Progress>,
+    started: Instant,
+    total_bytes: u64,
+    total_files: usize,
+    completed_bytes: AtomicU64,
+    transferred_bytes: AtomicU64,
+    done_files: AtomicUsize,
+}
+
+impl Events {
+    pub fn new(
+        listener: Option<Arc<dyn DownloadListener>>,
+        progress: Option<Progress>,
+        downloads: &[DownloadData],
+    ) -> Self {
+        let total_bytes = downloads.iter().map(|d| d.total_size).sum();
+        if let Some(progress) = &progress {
+            progress.lock().unwrap().setup(total_bytes);
+        }
+
+        Self {
+            listener,
+            progress,
+            started: Instant::now(),
+            total_bytes,
+            total_files: downloads.len(),
+            completed_bytes: AtomicU64::new(0),
+            transferred_bytes: AtomicU64::new(0),
+            done_files: AtomicUsize::new(0),
+        }
+    }
+
+    fn emit(&self, event: impl FnOnce() -> DownloadEvent) {
+        if let Some(listener) = &self.listener {
+            listener.on_event(&event());
+        }
+    }
+
+    pub fn stats(&self) -> DownloadStats {
+        let completed_bytes = self.completed_bytes.load(Ordering::SeqCst);
+        let transferred_bytes = self.transferred_bytes.load(Ordering::SeqCst);
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let bytes_per_second = if elapsed > 0.0 {
+            transferred_bytes as f64 / elapsed
+        } else {
+            0.0
+        };
+        let eta = (bytes_per_second > 0.0).then(|| {
+            Duration::from_secs_f64(
+                self.total_bytes.saturating_sub(completed_bytes) as f64 / bytes_per_second,
+            )
+        });
+
+        DownloadStats {
+            total_bytes: self.total_bytes,
+            completed_bytes,
+            transferred_bytes,
+            total_files: self.total_files,
+            done_files: self.done_files.load(Ordering::SeqCst),
+            bytes_per_second,
+            eta,
+        }
+    }
+
+    pub fn started(&self, download: &Arc<DownloadData>) {
+        self.emit(|| DownloadEvent::Started(download.clone()));
+    }
+
+    pub fn progress(&self, download: &Arc<DownloadData>, delta: u64, current: u64) {
+        self.completed_bytes.fetch_add(delta, Ordering::SeqCst);
+        self.transferred_bytes.fetch_add(delta, Ordering::SeqCst);
+        if let Some(progress) = &self.progress {
+            progress.lock().unwrap().progress(delta);
+        }
+        self.emit(|| DownloadEvent::Progress {
+            download: download.clone(),
+            current,
+            stats: self.stats(),
+        });
+    }
+
+    pub fn retrying(&self, download: &Arc<DownloadData>, url: &str, attempt: u16, delay: Duration) {
+        self.emit(|| DownloadEvent::Retrying {
+            download: download.clone(),
+            url: url.to_string(),
+            attempt,
+            delay,
+        });
+    }
+
+    pub fn verified(&self, download: &Arc<DownloadData>, status: &VerifyStatus) {
+        self.emit(|| DownloadEvent::Verified {
+            download: download.clone(),
+            status: status.clone(),
+        });
+    }
+
+    pub fn skipped(&self, download: &Arc<DownloadData>) {
+        self.completed_bytes
+            .fetch_add(download.total_size, Ordering::SeqCst);
+        self.done_files.fetch_add(1, Ordering::SeqCst);
+        self.emit(|| DownloadEvent::Skipped(download.clone()));
+    }
+
+    pub fn failed(&self, download: &Arc<DownloadData>, error: &DownloadError) {
+        self.done_files.fetch_add(1, Ordering::SeqCst);
+        self.emit(|| DownloadEvent::Failed {
+            download: download.clone(),
+            error: error.clone(),
+        });
+    }
+
+    pub fn finished(&self, download: &Arc<DownloadData>, output: &DownloadOutput) {
+        self.done_files.fetch_add(1, Ordering::SeqCst);
+        self.emit(|| DownloadEvent::Finished {
+            download: download.clone(),
+            output: output.clone(),
+        });
+    }
+
+    pub fn done(&self) {
+        if let Some(progress) = &self.progress {
+            progress.lock().unwrap().done();
+        }
+        self.emit(|| DownloadEvent::Done(self.stats()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio::sync::mpsc::unbounded_channel;
+
+    use crate::client::{
+        test_server::{sha1, temp_dir, TestServer},
+        DownloadCategory, DownloadData, DownloaderService,
+    };
+
+    use super::DownloadEvent;
+
+    #[test]
+    fn events_identify_each_file() {
+        let server = TestServer::start();
+        server.serve("/new.jar", b"new library");
+
+        let dir = temp_dir("events");
+        std::fs::write(dir.join("present.json"), b"present").unwrap();
+
+        let mut present =
+            DownloadData::new(&format!("{}/present.json", server.url()), "present.json")
+                .with_category(DownloadCategory::AssetIndex);
+        present.total_size = 7;
+        let mut new = DownloadData::new(&format!("{}/new.jar", server.url()), "new.jar")
+            .with_category(DownloadCategory::Library);
+        new.total_size = 11;
+        new.sha1 = sha1(b"new library");
+
+        let (sender, mut receiver) = unbounded_channel();
+        DownloaderService::new(dir.clone())
+            .with_downloads(vec![present, new])
+            .with_parallel_requests(1)
+            .with_listener(Arc::new(sender))
+            .run(None)
+            .unwrap();
+
+        let mut events = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            events.push(event);
+        }
+
+        assert!(matches!(&events[0], DownloadEvent::Started(d) if d.file_name() == "present.json"));
+        assert!(
+            matches!(&events[1], DownloadEvent::Skipped(d) if d.category() == DownloadCategory::AssetIndex)
+        );
+        assert!(matches!(&events[2], DownloadEvent::Started(d) if d.file_name() == "new.jar"));
+        assert!(events.iter().any(|e| matches!(
+            e,
+            DownloadEvent::Progress { download, current: 11, .. }
+                if download.category() == DownloadCategory::Library
+        )));
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, DownloadEvent::Verified { .. })));
+        assert!(events.iter().any(
+            |e| matches!(e, DownloadEvent::Finished { download, .. } if download.file_name() == "new.jar")
+        ));
+        match events.last() {
+            Some(DownloadEvent::Done(stats)) => {
+                assert_eq!(stats.total_bytes, 18);
+                assert_eq!(stats.completed_bytes, 18);
+                assert_eq!(stats.transferred_bytes, 11);
+                assert_eq!(stats.done_files, 2);
+            }
+            e => panic!("unexpected last event {e:?}"),
+        }
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,279 @@
+use std::{
+    fs,
+    io::{self, Read},
+    path::{Component, Path},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::ClientDownloaderError;
+
+/// Name of the file recording where an extracted Java came from.
+pub const JAVA_MARKER: &str = ".mc_downloader.json";
+
+/// Written into a Java directory once its archive is fully extracted.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JavaMarker {
+    /// The URL the archive was downloaded from.
+    pub url: String,
+    /// Hex SHA-256 of the archive.
+    pub sha256: String,
+}
+
+impl JavaMarker {
+    /// The marker of the Java in `dir`, if it was installed completely.
+    pub fn read(dir: &Path) -> Option<Self> {
+        serde_json::from_slice(&fs::read(dir.join(JAVA_MARKER)).ok()?).ok()
+    }
+
+    pub fn write(&self, dir: &Path) -> Result<(), ClientDownloaderError> {
+        fs::write(dir.join(JAVA_MARKER), serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Hex SHA-256 of the file at `path`.
+pub(crate) fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Extracts a `.tar.gz` or `.zip` Java archive into `dir`, keeping permissions and symlinks.
+///
+/// The single top-level folder archives usually wrap the JDK in (e.g. `jdk-17.0.8/`) is
+/// dropped, so `dir` ends up holding `bin/` or, on macOS, `Contents/`. Whatever was in `dir`
+/// before is replaced.
+pub fn extract_java_archive(archive: &Path, dir: &Path) -> Result<(), ClientDownloaderError> {
+    let mut staging = dir.as_os_str().to_os_string();
+    staging.push(".extracting");
+    let staging = Path::new(&staging);
+    if staging.exists() {
+        fs::remove_dir_all(staging)?;
+    }
+    fs::create_dir_all(staging)?;
+
+    let name = archive.to_string_lossy();
+    if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(fs::File::open(archive)?));
+        tar.set_preserve_permissions(true);
+        tar.unpack(staging)?;
+    } else {
+        extract_zip(archive, staging, |_| true)?;
+    }
+
+    let entries = fs::read_dir(staging)?.collect::<Result<Vec<_>, _>>()?;
+    let root = match entries.as_slice() {
+        [entry] if entry.file_type()?.is_dir() => entry.path(),
+        _ => staging.to_path_buf(),
+    };
+
+    if dir.exists() {
+        fs::remove_dir_all(dir)?;
+    }
+    fs::rename(&root, dir)?;
+    if staging.exists() {
+        fs::remove_dir_all(staging)?;
+    }
+    Ok(())
+}
+
+/// Extracts the entries of the zip `archive` whose name passes `keep` into `dir`, keeping
+/// Unix permissions and symlinks where the archive records them.
+///
+/// Symlinks may only point to relative targets without `..`, and nothing is written through
+/// a symlink, so no entry ends up outside `dir`.
+pub(crate) fn extract_zip(
+    archive: &Path,
+    dir: &Path,
+    keep: impl Fn(&str) -> bool,
+) -> Result<(), ClientDownloaderError> {
+    let mut archive = zip::ZipArchive::new(fs::File::open(archive)?)?;
+    fs::create_dir_all(dir)?;
+
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        if !keep(entry.name()) {
+            continue;
+        }
+        // Entries escaping the directory are skipped rather than trusted.
+        let Some(relative) = entry.enclosed_name().map(Path::to_path_buf) else {
+            continue;
+        };
+        if through_symlink(dir, &relative) {
+            return Err(ClientDownloaderError::UnsafeArchiveEntry(
+                entry.name().to_string(),
+            ));
+        }
+        let path = dir.join(relative);
+        if entry.is_dir() {
+            fs::create_dir_all(&path)?;
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mode = entry.unix_mode();
+        if mode.is_some_and(|m| m & 0o170000 == 0o120000) {
+            let mut target = String::new();
+            entry.read_to_string(&mut target)?;
+            let escapes = Path::new(&target)
+                .components()
+                .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir));
+            if escapes {
+                return Err(ClientDownloaderError::UnsafeArchiveEntry(
+                    entry.name().to_string(),
+                ));
+            }
+            symlink(&target, &path)?;
+            continue;
+        }
+
+        io::copy(&mut entry, &mut fs::File::create(&path)?)?;
+        #[cfg(unix)]
+        if let Some(mode) = mode {
+            use std::os::unix::fs::PermissionsExt;
+
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode & 0o7777))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether `relative` below `dir`, or any directory on the way, is a symlink.
+fn through_symlink(dir: &Path, relative: &Path) -> bool {
+    let mut path = dir.to_path_buf();
+    relative.components().any(|component| {
+        path.push(component);
+        path.symlink_metadata()
+            .is_ok_and(|metadata| metadata.file_type().is_symlink())
+    })
+}
+
+#[cfg(unix)]
+fn symlink(target: &str, path: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, path)
+}
+
+#[cfg(not(unix))]
+fn symlink(target: &str, path: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("can't create symlink {} -> {target}", path.display()),
+    ))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{io::Write, os::unix::fs::PermissionsExt, path::Path};
+
+    use crate::client::test_server::{tar_gz, temp_dir};
+
+    use crate::error::ClientDownloaderError;
+
+    use super::{extract_java_archive, extract_zip, JavaMarker};
+
+    fn zip() -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options = |mode| zip::write::FileOptions::default().unix_permissions(mode);
+        writer
+            .start_file("jdk-17.0.8/bin/java", options(0o755))
+            .unwrap();
+        writer.write_all(b"#!/bin/sh\n").unwrap();
+        writer
+            .start_file("jdk-17.0.8/release", options(0o644))
+            .unwrap();
+        writer.write_all(b"JAVA_VERSION=\"17.0.8\"\n").unwrap();
+        writer
+            .add_symlink("jdk-17.0.8/lib/libjli.so.1", "libjli.so", options(0o777))
+            .unwrap();
+        writer.start_file("../escape", options(0o644)).unwrap();
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn assert_layout(dir: &Path) {
+        let mode = std::fs::metadata(dir.join("bin/java"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o755);
+        assert!(dir.join("release").is_file());
+        assert_eq!(
+            std::fs::read_link(dir.join("lib/libjli.so.1")).unwrap(),
+            Path::new("libjli.so")
+        );
+    }
+
+    #[test]
+    fn extracts_and_normalises_archives() {
+        let dir = temp_dir("java-archive");
+
+        std::fs::write(dir.join("jdk.tar.gz"), tar_gz("jdk-17.0.8")).unwrap();
+        std::fs::create_dir_all(dir.join("17.0.8/stale")).unwrap();
+        extract_java_archive(&dir.join("jdk.tar.gz"), &dir.join("17.0.8")).unwrap();
+        assert_layout(&dir.join("17.0.8"));
+        assert!(!dir.join("17.0.8/stale").exists());
+        assert!(!dir.join("17.0.8.extracting").exists());
+
+        std::fs::write(dir.join("jdk.zip"), zip()).unwrap();
+        extract_java_archive(&dir.join("jdk.zip"), &dir.join("zip")).unwrap();
+        assert_layout(&dir.join("zip"));
+        assert!(!dir.join("escape").exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn escaping_symlinks_are_rejected() {
+        let dir = temp_dir("java-archive-symlinks");
+        let options = zip::write::FileOptions::default().unix_permissions(0o777);
+        let archive = |link: &str, target: &str, through: Option<&str>| {
+            let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+            writer.add_symlink(link, target, options).unwrap();
+            if let Some(name) = through {
+                writer.start_file(name, options).unwrap();
+                writer.write_all(b"escaped").unwrap();
+            }
+            writer.finish().unwrap().into_inner()
+        };
+        let extract = |bytes: Vec<u8>| {
+            std::fs::write(dir.join("evil.zip"), bytes).unwrap();
+            extract_zip(&dir.join("evil.zip"), &dir.join("out"), |_| true)
+        };
+
+        assert!(matches!(
+            extract(archive("lib", "/tmp", None)),
+            Err(ClientDownloaderError::UnsafeArchiveEntry(_))
+        ));
+        assert!(matches!(
+            extract(archive("lib", "../..", None)),
+            Err(ClientDownloaderError::UnsafeArchiveEntry(_))
+        ));
+        // Nothing is written through a link, even one pointing inside.
+        assert!(matches!(
+            extract(archive("lib", "sub", Some("lib/escape"))),
+            Err(ClientDownloaderError::UnsafeArchiveEntry(_))
+        ));
+        assert!(!dir.join("out/sub/escape").exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn marker_round_trip() {
+        let dir = temp_dir("java-marker");
+        assert!(JavaMarker::read(&dir).is_none());
+
+        let marker = JavaMarker {
+            url: "https://example.com/jdk.tar.gz".to_string(),
+            sha256: "00".repeat(32),
+        };
+        marker.write(&dir).unwrap();
+        assert_eq!(JavaMarker::read(&dir), Some(marker));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,266 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    error::{ClientDownloaderError, ManifestError},
+    manifest::{Manifest, ManifestComponent},
+};
+
+use super::{client_downloader::block_on, runtime_path, ClientDownloader, Progress};
+
+/// A Java installation found on disk.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JavaInstallation {
+    /// The directory holding `bin/` and the `release` file.
+    pub home: PathBuf,
+    pub executable: PathBuf,
+    /// `JAVA_VERSION` from the `release` file, e.g. `17.0.8` or `1.8.0_51`.
+    pub version: String,
+    pub major_version: u32,
+}
+
+impl JavaInstallation {
+    /// Reads the installation at `path` from its `release` file, also looking into the
+    /// `Contents/Home` of a macOS bundle.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        [
+            path.to_path_buf(),
+            path.join("Contents/Home"),
+            path.join("jre.bundle/Contents/Home"),
+        ]
+        .into_iter()
+        .find_map(|home| {
+            let release = std::fs::read_to_string(home.join("release")).ok()?;
+            let version = release_version(&release)?;
+            let executable = home
+                .join("bin")
+                .join(if cfg!(windows) { "java.exe" } else { "java" });
+            if !executable.is_file() {
+                return None;
+            }
+            Some(Self {
+                major_version: major_version(&version)?,
+                home,
+                executable,
+                version,
+            })
+        })
+    }
+}
+
+/// `JAVA_VERSION` of a JDK's `release` file.
+pub fn release_version(release: &str) -> Option<String> {
+    release.lines().find_map(|line| {
+        let value = line.strip_prefix("JAVA_VERSION=")?;
+        Some(value.trim().trim_matches('"').to_string())
+    })
+}
+
+/// The major version of a Java version string: `8` for `1.8.0_51`, `17` for `17.0.8`.
+pub fn major_version(version: &str) -> Option<u32> {
+    let mut parts = version.split(|c: char| !c.is_ascii_digit());
+    match parts.next()?.parse().ok()? {
+        1 => parts.next()?.parse().ok(),
+        major => Some(major),
+    }
+}
+
+/// Finds the Java a version needs, downloading Mojang's runtime for it if none is installed.
+///
+/// Installations are looked for in the managed runtimes directory first, then `JAVA_HOME`,
+/// then the configured paths. Only the exact major version is accepted, as old versions of
+/// the game break on newer Java and new ones don't start on older Java.
+#[derive(Clone, Debug)]
+pub struct JavaResolver {
+    runtimes_path: PathBuf,
+    java_home: Option<PathBuf>,
+    paths: Vec<PathBuf>,
+}
+
+impl JavaResolver {
+    /// A resolver managing runtimes in `runtimes_path`, see [`runtime_path`].
+    pub fn new(runtimes_path: PathBuf) -> Self {
+        Self {
+            runtimes_path,
+            java_home: std::env::var_os("JAVA_HOME").map(PathBuf::from),
+            paths: Vec::new(),
+        }
+    }
+
+    /// Overrides the `JAVA_HOME` read from the environment.
+    pub fn with_java_home(&mut self, java_home: Option<PathBuf>) -> &mut Self {
+        self.java_home = java_home;
+        self
+    }
+
+    /// Adds a Java installation to consider.
+    pub fn with_path(&mut self, path: PathBuf) -> &mut Self {
+        self.paths.push(path);
+        self
+    }
+
+    /// An installed Java matching `java_version`, if any.
+    pub fn find(&self, java_version: &ManifestComponent) -> Option<JavaInstallation> {
+        let major = u32::try_from(java_version.major_version).ok()?;
+        std::iter::once(runtime_path(&self.runtimes_path, &java_version.component))
+            .chain(self.java_home.clone())
+            .chain(self.paths.iter().cloned())
+            .filter_map(|path| JavaInstallation::from_path(&path))
+            .find(|java| java.major_version == major)
+    }
+
+    /// The `java` executable to launch `manifest` with, downloading the runtime component
+    /// it names through `downloader` if needed.
+    pub fn resolve(
+        &self,
+        downloader: &ClientDownloader,
+        manifest: &Manifest,
+        progress: Option<Progress>,
+    ) -> Result<PathBuf, ClientDownloaderError> {
+        block_on(self.resolve_async(downloader, manifest, progress))?
+    }
+
+    pub async fn resolve_async(
+        &self,
+        downloader: &ClientDownloader,
+        manifest: &Manifest,
+        progress: Option<Progress>,
+    ) -> Result<PathBuf, ClientDownloaderError> {
+        if let Some(java) = self.find(&manifest.java_version) {
+            return Ok(java.executable);
+        }
+
+        let results = downloader
+            .download_java_runtime_async(
+                &self.runtimes_path,
+                &manifest.java_version.component,
+                progress,
+            )
+            .await?;
+        for result in results {
+            result?;
+        }
+
+        self.find(&manifest.java_version)
+            .map(|java| java.executable)
+            .ok_or(ClientDownloaderError::Manifest(
+                ManifestError::JavaBinNotExist,
+            ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{
+        client::{
+            test_server::{serve_java_runtime, temp_dir, TestServer},
+            ClientDownloader, Endpoints,
+        },
+        manifest::ManifestComponent,
+        rules::Platform,
+    };
+
+    use super::{major_version, release_version, JavaInstallation, JavaResolver};
+
+    fn install(home: &Path, version: &str) {
+        std::fs::create_dir_all(home.join("bin")).unwrap();
+        std::fs::write(home.join("bin/java"), "").unwrap();
+        std::fs::write(
+            home.join("release"),
+            format!("IMPLEMENTOR=\"Microsoft\"\nJAVA_VERSION=\"{version}\"\n"),
+        )
+        .unwrap();
+    }
+
+    fn component(component: &str, major_version: i8) -> ManifestComponent {
+        ManifestComponent {
+            component: component.to_string(),
+            major_version,
+        }
+    }
+
+    #[test]
+    fn versions() {
+        assert_eq!(
+            release_version("JAVA_RUNTIME_VERSION=\"17.0.8+7\"\nJAVA_VERSION=\"17.0.8\"\n")
+                .as_deref(),
+            Some("17.0.8")
+        );
+        assert_eq!(major_version("1.8.0_51"), Some(8));
+        assert_eq!(major_version("17.0.8"), Some(17));
+        assert_eq!(major_version("21"), Some(21));
+        assert_eq!(major_version("jdk"), None);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn finds_matching_installation() {
+        let dir = temp_dir("java-resolver");
+        install(&dir.join("runtimes/jre-legacy"), "1.8.0_51");
+        install(&dir.join("home"), "21.0.3");
+        install(&dir.join("jdk-17.jdk/Contents/Home"), "17.0.8");
+
+        let mut resolver = JavaResolver::new(dir.join("runtimes"));
+        resolver
+            .with_java_home(Some(dir.join("home")))
+            .with_path(dir.join("jdk-17.jdk"));
+
+        let java = resolver.find(&component("jre-legacy", 8)).unwrap();
+        assert_eq!(java.executable, dir.join("runtimes/jre-legacy/bin/java"));
+        let java = resolver.find(&component("java-runtime-delta", 21)).unwrap();
+        assert_eq!(java.home, dir.join("home"));
+        let java = resolver.find(&component("java-runtime-gamma", 17)).unwrap();
+        assert_eq!(
+            java,
+            JavaInstallation {
+                home: dir.join("jdk-17.jdk/Contents/Home"),
+                executable: dir.join("jdk-17.jdk/Contents/Home/bin/java"),
+                version: "17.0.8".to_string(),
+                major_version: 17,
+            }
+        );
+        assert!(resolver
+            .find(&component("java-runtime-alpha", 16))
+            .is_none());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn downloads_missing_runtime() {
+        let server = TestServer::start();
+        let release = b"JAVA_VERSION=\"17.0.8\"\n";
+        let java_runtimes = serve_java_runtime(
+            &server,
+            &[("bin/java", b"", true), ("release", release, false)],
+            &[],
+        );
+
+        let mut downloader = ClientDownloader::with_manifest(
+            Endpoints {
+                java_runtimes,
+                ..Default::default()
+            },
+            Default::default(),
+        );
+        downloader.platform = Platform::new("linux", "x86_64");
+
+        let dir = temp_dir("java-resolver-download");
+        let mut resolver = JavaResolver::new(dir.clone());
+        resolver.with_java_home(None);
+        let mut manifest: crate::manifest::Manifest =
+            serde_json::from_str(include_str!("../../tests/fixtures/versions/1.12.2.json"))
+                .unwrap();
+        manifest.java_version = component("java-runtime-gamma", 17);
+
+        let java = resolver.resolve(&downloader, &manifest, None).unwrap();
+        assert_eq!(java, dir.join("java-runtime-gamma/bin/java"));
+        // Found on disk from now on.
+        resolver.resolve(&downloader, &manifest, None).unwrap();
+        assert_eq!(server.requests("/java-runtimes.json").len(), 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,270 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::rules::Platform;
+
+use super::{DownloadCategory, DownloadData, Encoding};
+
+/// Mojang's Java runtime index (`all.json`): releases by platform, then by component such as
+/// `java-runtime-gamma` or `jre-legacy`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RuntimeIndex(pub BTreeMap<String, BTreeMap<String, Vec<RuntimeRelease>>>);
+
+impl RuntimeIndex {
+    /// The release of `component` for `platform`, if Mojang ships one.
+    pub fn release(&self, platform: &Platform, component: &str) -> Option<&RuntimeRelease> {
+        self.0
+            .get(runtime_platform(platform)?)?
+            .get(component)?
+            .first()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RuntimeRelease {
+    pub manifest: RuntimeDownload,
+    pub version: RuntimeVersion,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RuntimeVersion {
+    pub name: String,
+    pub released: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RuntimeDownload {
+    pub sha1: String,
+    pub size: u64,
+    pub url: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RuntimeFileDownloads {
+    pub raw: RuntimeDownload,
+    pub lzma: Option<RuntimeDownload>,
+}
+
+/// An entry of a runtime's file manifest.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuntimeFile {
+    File {
+        downloads: RuntimeFileDownloads,
+        #[serde(default)]
+        executable: bool,
+    },
+    Directory,
+    Link {
+        target: String,
+    },
+}
+
+/// The files a Java runtime release consists of, keyed by their path in the runtime.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RuntimeManifest {
+    pub files: BTreeMap<String, RuntimeFile>,
+}
+
+impl RuntimeManifest {
+    /// Entries whose path stays inside the runtime directory; the rest are never trusted.
+    fn entries(&self) -> impl Iterator<Item = (&String, &RuntimeFile)> {
+        self.files.iter().filter(|(name, _)| {
+            Path::new(name)
+                .components()
+                .all(|c| matches!(c, Component::Normal(_)))
+        })
+    }
+
+    /// The files to download into the runtime directory, relative to it.
+    ///
+    /// Files with an `lzma` variant are fetched compressed and checked against the `raw` one.
+    pub fn downloads(&self) -> Vec<DownloadData> {
+        self.entries()
+            .filter_map(|(name, file)| match file {
+                RuntimeFile::File { downloads, .. } => Some(DownloadData {
+                    url: downloads
+                        .lzma
+                        .as_ref()
+                        .unwrap_or(&downloads.raw)
+                        .url
+                        .clone(),
+                    file_name: name.clone(),
+                    output_path: name.clone(),
+                    sha1: downloads.raw.sha1.clone(),
+                    total_size: downloads.raw.size,
+                    category: DownloadCategory::Java,
+                    encoding: match downloads.lzma {
+                        Some(_) => Encoding::Lzma,
+                        None => Encoding::Identity,
+                    },
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Creates the directories of the runtime under `path`.
+    pub fn create_directories(&self, path: &Path) -> io::Result<()> {
+        for (name, file) in self.entries() {
+            if let RuntimeFile::Directory = file {
+                fs::create_dir_all(path.join(name))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Once the files are downloaded into `path`, marks the executables as such and creates
+    /// the symlinks.
+    ///
+    /// Links are only created on Unix, where Mojang's runtimes use them.
+    pub fn finish(&self, path: &Path) -> io::Result<()> {
+        for (name, file) in self.entries() {
+            let target_path = path.join(name);
+            match file {
+                RuntimeFile::File {
+                    executable: true, ..
+                } if target_path.is_file() => set_executable(&target_path)?,
+                RuntimeFile::Link { target } => link(target, &target_path)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The platform name the runtime index uses for `platform`, if Mojang builds runtimes for
+/// it at all.
+pub fn runtime_platform(platform: &Platform) -> Option<&'static str> {
+    Some(match (platform.os.as_str(), platform.arch.as_str()) {
+        ("linux", "x86_64") => "linux",
+        ("linux", "x86") => "linux-i386",
+        ("osx", "x86_64") => "mac-os",
+        ("osx", "arm64") => "mac-os-arm64",
+        ("windows", "x86_64") => "windows-x64",
+        ("windows", "x86") => "windows-x86",
+        ("windows", "arm64") => "windows-arm64",
+        _ => return None,
+    })
+}
+
+/// Where `component` is installed under `root_path`.
+pub fn runtime_path(root_path: &Path, component: &str) -> PathBuf {
+    root_path.join(component)
+}
+
+#[cfg(unix)]
+fn set_executable(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut permissions = fs::metadata(path)?.permissions();
+    permissions.set_mode(permissions.mode() | 0o755);
+    fs::set_permissions(path, permissions)
+}
+
+#[cfg(not(unix))]
+fn set_executable(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn link(target: &str, path: &Path) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if path.symlink_metadata().is_ok() {
+        fs::remove_file(path)?;
+    }
+    std::os::unix::fs::symlink(target, path)
+}
+
+#[cfg(not(unix))]
+fn link(_target: &str, _path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rules::Platform;
+
+    use crate::client::Encoding;
+
+    use super::{runtime_platform, RuntimeFile, RuntimeIndex, RuntimeManifest};
+
+    const INDEX: &str = include_str!("../../tests/fixtures/java/all.json");
+    const MANIFEST: &str = include_str!("../../tests/fixtures/java/java-runtime-gamma-linux.json");
+
+    #[test]
+    fn index_release_by_platform() {
+        let index: RuntimeIndex = serde_json::from_str(INDEX).unwrap();
+
+        let gamma = index
+            .release(&Platform::new("linux", "x86_64"), "java-runtime-gamma")
+            .unwrap();
+        assert_eq!(gamma.version.name, "17.0.8");
+        assert!(gamma.manifest.url.ends_with("/manifest.json"));
+        // Listed for the platform, but without any release.
+        assert!(index
+            .release(&Platform::new("linux", "x86"), "java-runtime-gamma")
+            .is_none());
+        assert!(index
+            .release(&Platform::new("osx", "arm64"), "jre-legacy")
+            .is_none());
+        assert!(index
+            .release(&Platform::new("osx", "arm64"), "java-runtime-gamma")
+            .is_some());
+    }
+
+    #[test]
+    fn platform_names() {
+        let name = |os, arch| runtime_platform(&Platform::new(os, arch));
+
+        assert_eq!(name("linux", "x86_64"), Some("linux"));
+        assert_eq!(name("linux", "x86"), Some("linux-i386"));
+        assert_eq!(name("osx", "x86_64"), Some("mac-os"));
+        assert_eq!(name("osx", "arm64"), Some("mac-os-arm64"));
+        assert_eq!(name("windows", "x86_64"), Some("windows-x64"));
+        assert_eq!(name("windows", "arm64"), Some("windows-arm64"));
+        // No runtime is better than one built for another architecture.
+        assert_eq!(name("linux", "arm64"), None);
+        assert_eq!(name("osx", "riscv64"), None);
+        assert_eq!(name("windows", "arm"), None);
+        assert_eq!(name("freebsd", "x86_64"), None);
+    }
+
+    #[test]
+    fn manifest_entries() {
+        let manifest: RuntimeManifest = serde_json::from_str(MANIFEST).unwrap();
+
+        assert!(matches!(
+            manifest.files["bin/java"],
+            RuntimeFile::File {
+                executable: true,
+                ..
+            }
+        ));
+        assert!(matches!(manifest.files["bin"], RuntimeFile::Directory));
+        assert!(
+            matches!(&manifest.files["lib/libjli.so.link"], RuntimeFile::Link { target } if target == "libjli.so")
+        );
+
+        let downloads = manifest.downloads();
+        let names = downloads.iter().map(|d| d.file_name()).collect::<Vec<_>>();
+        // `../escape` is left out.
+        assert_eq!(names, ["bin/java", "lib/libjli.so", "release"]);
+
+        // The compressed variant is preferred, but checked against the raw file.
+        assert_eq!(downloads[0].encoding(), Encoding::Identity);
+        assert_eq!(downloads[1].encoding(), Encoding::Lzma);
+        assert!(downloads[1]
+            .url()
+            .starts_with("https://piston-data.mojang.com/v1/objects/5b3e"));
+        assert_eq!(downloads[1].total_size(), 67216);
+    }
+}
@@ -0,0 +1,89 @@
+/// Ordered fallback hosts, grouped by the logical host they stand in for.
+///
+/// When a download's URL starts with one of the base URLs of a group, every base URL of that
+/// group is tried in order until one of them serves a verified file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Mirrors {
+    groups: Vec<Vec<String>>,
+}
+
+impl Mirrors {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds a logical host as an ordered list of interchangeable base URLs,
+    /// e.g. the official host, then a public mirror, then a LAN cache.
+    pub fn with_host<I, S>(&mut self, bases: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let bases = bases
+            .into_iter()
+            .map(|b| b.into().trim_end_matches('/').to_string())
+            .collect::<Vec<String>>();
+        if !bases.is_empty() {
+            self.groups.push(bases);
+        }
+        self
+    }
+
+    /// Every URL `url` can be fetched from, in the order they should be tried.
+    pub fn candidates(&self, url: &str) -> Vec<String> {
+        for group in &self.groups {
+            let Some(path) = group.iter().find_map(|base| strip_base(url, base)) else {
+                continue;
+            };
+            return group.iter().map(|base| format!("{base}{path}")).collect();
+        }
+
+        vec![url.to_string()]
+    }
+}
+
+/// The path of `url` below `base`, if `url` is on that host rather than merely sharing a prefix
+/// with it.
+pub(crate) fn strip_base<'a>(url: &'a str, base: &str) -> Option<&'a str> {
+    url.strip_prefix(base)
+        .filter(|path| path.is_empty() || path.starts_with('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Mirrors;
+
+    #[test]
+    fn candidates_follow_group_order() {
+        let mut mirrors = Mirrors::new();
+        mirrors.with_host([
+            "https://libraries.minecraft.net",
+            "https://bmclapi2.bangbang93.com/maven/",
+            "http://cache.lan/libraries",
+        ]);
+
+        assert_eq!(
+            mirrors.candidates("https://bmclapi2.bangbang93.com/maven/org/lwjgl/lwjgl.jar"),
+            vec![
+                "https://libraries.minecraft.net/org/lwjgl/lwjgl.jar",
+                "https://bmclapi2.bangbang93.com/maven/org/lwjgl/lwjgl.jar",
+                "http://cache.lan/libraries/org/lwjgl/lwjgl.jar",
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_host_has_single_candidate() {
+        let mut mirrors = Mirrors::new();
+        mirrors.with_host(["https://libraries.minecraft.net"]);
+
+        assert_eq!(
+            mirrors.candidates("https://libraries.minecraft.net.evil/lwjgl.jar"),
+            vec!["https://libraries.minecraft.net.evil/lwjgl.jar"]
+        );
+        assert_eq!(
+            mirrors.candidates("https://maven.fabricmc.net/loader.jar"),
+            vec!["https://maven.fabricmc.net/loader.jar"]
+        );
+    }
+}
@@ -1,18 +1,42 @@
 mod client_downloader;
+mod control;
 mod downloader;
+mod endpoints;
+mod events;
+mod java_archive;
+mod java_resolver;
+mod java_runtime;
+mod mirrors;
+mod natives;
+mod retry;
+mod skip;
+#[cfg(test)]
+pub(crate) mod test_server;
 mod verify;
 
 use std::{
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
 };
 
 pub use client_downloader::*;
+pub use control::*;
 pub use downloader::*;
+pub use endpoints::*;
+pub use events::{DownloadEvent, DownloadListener, DownloadStats};
+pub use java_archive::{extract_java_archive, JavaMarker, JAVA_MARKER};
+pub use java_resolver::*;
+pub use java_runtime::*;
+pub use mirrors::*;
+pub use natives::extract_natives;
+pub use retry::{RetryPolicy, DEFAULT_RETRY_BUDGET};
+pub use skip::SkipPolicy;
 pub use verify::*;
 
+use async_trait::async_trait;
+
 use crate::{
-    error::{ClientDownloaderError, DownloadError},
+    error::{ClientDownloaderError, DownloadError, JavaDownloadError},
     manifest::Manifest,
 };
 
@@ -23,7 +47,11 @@ pub struct DownloadOutput {
     pub status: u16,
     pub file_name: String,
     pub file_path: PathBuf,
+    /// The URL the file was served from; empty if it was already present.
+    pub url: String,
     pub verified: VerifyStatus,
+    /// An existing file was corrupted and has been downloaded again.
+    pub repaired: bool,
 }
 
 /// A Progress reporter to use for the `Download`
@@ -42,7 +70,7 @@ pub trait DownloadVersion {
     fn download_version(
         &self,
         _version_id: &str,
-        _game_path: &PathBuf,
+        _game_path: &Path,
         _manifest_path: Option<&PathBuf>,
         _version_path: Option<&PathBuf>,
         _progress: Option<Progress>,
@@ -51,7 +79,7 @@ pub trait DownloadVersion {
     fn download_by_manifest(
         &self,
         _manifest: &Manifest,
-        _game_path: &PathBuf,
+        _game_path: &Path,
         _version_path: Option<&PathBuf>,
         _progress: Option<Progress>,
     ) -> Result<Vec<DownloadResult>, ClientDownloaderError>;
@@ -59,7 +87,44 @@ pub trait DownloadVersion {
 
 pub trait DownloadJava {
     fn check_version(&self, _root_path: &str, _expected_version: &str) -> bool;
-    fn download_java(&self, _root_path: &str, _version: &str, _progress: Option<Progress>);
+    fn download_java(
+        &self,
+        _root_path: &str,
+        _version: &str,
+        _progress: Option<Progress>,
+    ) -> Result<(), JavaDownloadError>;
+}
+
+/// Async counterpart of [`DownloadVersion`], running on the caller's executor.
+#[async_trait]
+pub trait AsyncDownloadVersion {
+    async fn download_version_async(
+        &self,
+        _version_id: &str,
+        _game_path: &Path,
+        _manifest_path: Option<&PathBuf>,
+        _version_path: Option<&PathBuf>,
+        _progress: Option<Progress>,
+    ) -> Result<Vec<DownloadResult>, ClientDownloaderError>;
+
+    async fn download_by_manifest_async(
+        &self,
+        _manifest: &Manifest,
+        _game_path: &Path,
+        _version_path: Option<&PathBuf>,
+        _progress: Option<Progress>,
+    ) -> Result<Vec<DownloadResult>, ClientDownloaderError>;
+}
+
+/// Async counterpart of [`DownloadJava`], running on the caller's executor.
+#[async_trait]
+pub trait AsyncDownloadJava {
+    async fn download_java_async(
+        &self,
+        _root_path: &str,
+        _version: &str,
+        _progress: Option<Progress>,
+    ) -> Result<(), JavaDownloadError>;
 }
 
 fn download_result_to_fmt(
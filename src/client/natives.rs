@@ -0,0 +1,126 @@
+use std::path::Path;
+
+use crate::{
+    error::ClientDownloaderError,
+    manifest::{ManifestFile, ManifestLibrary},
+    rules::Platform,
+};
+
+use super::java_archive::extract_zip;
+
+impl ManifestLibrary {
+    /// The classifier of the natives jar for `platform`, with `${arch}` replaced by the
+    /// pointer width, if the library has natives for it.
+    pub fn native_classifier(&self, platform: &Platform) -> Option<String> {
+        let classifier = self.natives.as_ref()?.get(&platform.os)?;
+        let bits = if platform.arch == "x86" { "32" } else { "64" };
+        Some(classifier.replace("${arch}", bits))
+    }
+
+    /// The natives jar to download for `platform`.
+    pub fn native_artifact(&self, platform: &Platform) -> Option<&ManifestFile> {
+        let classifier = self.native_classifier(platform)?;
+        self.downloads.classifiers.as_ref()?.get(&classifier)
+    }
+
+    /// Entries of the natives jar that must not be extracted.
+    pub fn native_excludes(&self) -> &[String] {
+        self.extract.as_ref().map_or(&[], |e| &e.exclude)
+    }
+}
+
+/// Extracts the natives `jar` into `dir`, leaving out `META-INF/` and every entry starting
+/// with one of `exclude`.
+pub fn extract_natives(
+    jar: &Path,
+    dir: &Path,
+    exclude: &[String],
+) -> Result<(), ClientDownloaderError> {
+    extract_zip(jar, dir, |name| {
+        !name.starts_with("META-INF/") && !exclude.iter().any(|e| name.starts_with(e.as_str()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        client::test_server::{temp_dir, zip},
+        manifest::read_manifest_from_str,
+        rules::Platform,
+    };
+
+    use super::extract_natives;
+
+    const OLD_ALPHA: &str = include_str!("../../tests/fixtures/versions/a1.0.4.json");
+    const LEGACY: &str = include_str!("../../tests/fixtures/versions/1.12.2.json");
+
+    #[test]
+    fn classifier_per_platform_and_arch() {
+        let manifest = read_manifest_from_str(OLD_ALPHA).unwrap();
+        let platform = &manifest.libraries[1];
+        assert_eq!(
+            platform
+                .native_artifact(&Platform::new("linux", "x86_64"))
+                .unwrap()
+                .path
+                .as_deref(),
+            Some("org/lwjgl/lwjgl/lwjgl-platform/2.9.0/lwjgl-platform-2.9.0-natives-linux.jar")
+        );
+        assert!(manifest.libraries[0]
+            .native_classifier(&Platform::new("linux", "x86_64"))
+            .is_none());
+
+        let manifest = read_manifest_from_str(LEGACY).unwrap();
+        let twitch = &manifest.libraries[1];
+        assert_eq!(
+            twitch
+                .native_classifier(&Platform::new("windows", "x86"))
+                .as_deref(),
+            Some("natives-windows-32")
+        );
+        assert_eq!(
+            twitch
+                .native_artifact(&Platform::new("windows", "x86_64"))
+                .unwrap()
+                .size,
+            7457619
+        );
+        assert!(twitch
+            .native_artifact(&Platform::new("osx", "x86_64"))
+            .is_none());
+    }
+
+    #[test]
+    fn extract_skips_meta_inf_and_excludes() {
+        let dir = temp_dir("natives");
+        let jar = dir.join("natives.jar");
+        std::fs::write(
+            &jar,
+            zip(&[
+                ("META-INF/MANIFEST.MF", b"manifest"),
+                ("liblwjgl.so", b"lwjgl"),
+                ("linux/libopenal.so", b"openal"),
+                ("skip/me.txt", b"skipped"),
+                ("../escape.so", b"escape"),
+            ]),
+        )
+        .unwrap();
+
+        let natives = dir.join("out");
+        extract_natives(&jar, &natives, &["skip/".to_string()]).unwrap();
+
+        assert_eq!(
+            std::fs::read(natives.join("liblwjgl.so")).unwrap(),
+            b"lwjgl"
+        );
+        assert_eq!(
+            std::fs::read(natives.join("linux/libopenal.so")).unwrap(),
+            b"openal"
+        );
+        assert!(!natives.join("META-INF").exists());
+        assert!(!natives.join("skip").exists());
+        assert!(!dir.join("escape.so").exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,229 @@
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
+
+/// Retries a whole run may spend by default, enough to ride out a few flaky files without
+/// letting thousands of downloads retry against a dead host.
+pub const DEFAULT_RETRY_BUDGET: u32 = 100;
+
+/// Decides whether, and after how long, a failed request is tried again.
+///
+/// The delay grows exponentially from `base_delay` up to `max_delay`, optionally with random
+/// jitter, unless the server asks for a specific delay with `Retry-After`, which is still
+/// capped at `max_delay`. A `budget` caps the retries of a whole run, so a dead host fails
+/// fast instead of every download retrying; by default a run gets [`DEFAULT_RETRY_BUDGET`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    max_attempts: u16,
+    base_delay: Duration,
+    max_delay: Duration,
+    jitter: bool,
+    budget: Option<u32>,
+    retryable_statuses: Vec<u16>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            jitter: true,
+            budget: Some(DEFAULT_RETRY_BUDGET),
+            retryable_statuses: vec![408, 425, 429, 500, 502, 503, 504],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that gives up after the first failure.
+    pub fn never() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Maximum attempts per URL, including the first one.
+    pub fn with_max_attempts(&mut self, max_attempts: u16) -> &mut Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    pub fn with_base_delay(&mut self, base_delay: Duration) -> &mut Self {
+        self.base_delay = base_delay;
+        self
+    }
+
+    pub fn with_max_delay(&mut self, max_delay: Duration) -> &mut Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    pub fn with_jitter(&mut self, jitter: bool) -> &mut Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Caps the total amount of retries across every download of a run; `None` lifts the cap.
+    pub fn with_budget(&mut self, budget: Option<u32>) -> &mut Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Replaces the HTTP status codes that are worth retrying.
+    pub fn with_retryable_statuses(&mut self, statuses: Vec<u16>) -> &mut Self {
+        self.retryable_statuses = statuses;
+        self
+    }
+
+    pub fn max_attempts(&self) -> u16 {
+        self.max_attempts
+    }
+
+    pub fn is_retryable_status(&self, status: u16) -> bool {
+        self.retryable_statuses.contains(&status)
+    }
+
+    /// Connection failures, timeouts and requests or bodies cut off mid-transfer, such as by a
+    /// reset connection, are transient; a malformed URL or a redirect loop won't get better by
+    /// trying again.
+    pub fn is_retryable_error(&self, error: &reqwest::Error) -> bool {
+        error.is_timeout() || error.is_connect() || error.is_body() || error.is_request()
+    }
+
+    /// The delay before attempt `attempt + 1`, `attempt` starting at 1.
+    pub fn delay(&self, attempt: u16, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after.min(self.max_delay);
+        }
+
+        let exponent = u32::from(attempt.saturating_sub(1)).min(16);
+        let delay = self
+            .base_delay
+            .saturating_mul(1 << exponent)
+            .min(self.max_delay);
+
+        if self.jitter {
+            // Equal jitter: keep half the delay, randomise the other half.
+            let half = delay / 2;
+            half + half.mul_f64(fastrand::f64())
+        } else {
+            delay
+        }
+    }
+
+    pub(crate) fn budget(&self) -> RetryBudget {
+        RetryBudget(self.budget.map(|b| Arc::new(AtomicU32::new(b))))
+    }
+}
+
+/// The retries left for a run, shared between its downloads.
+#[derive(Clone, Debug)]
+pub(crate) struct RetryBudget(Option<Arc<AtomicU32>>);
+
+impl RetryBudget {
+    /// Takes one retry from the budget, returning `false` once it is exhausted.
+    pub fn try_spend(&self) -> bool {
+        match &self.0 {
+            Some(left) => left
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok(),
+            None => true,
+        }
+    }
+}
+
+/// Parses a `Retry-After` header value, either delay-seconds or an HTTP date.
+pub(crate) fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(
+        date.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::{parse_retry_after, RetryPolicy};
+
+    #[test]
+    fn delay_grows_exponentially_up_to_max() {
+        let mut policy = RetryPolicy::default();
+        policy
+            .with_base_delay(Duration::from_secs(1))
+            .with_max_delay(Duration::from_secs(5))
+            .with_jitter(false);
+
+        assert_eq!(policy.delay(1, None), Duration::from_secs(1));
+        assert_eq!(policy.delay(2, None), Duration::from_secs(2));
+        assert_eq!(policy.delay(3, None), Duration::from_secs(4));
+        assert_eq!(policy.delay(4, None), Duration::from_secs(5));
+        assert_eq!(
+            policy.delay(2, Some(Duration::from_secs(3))),
+            Duration::from_secs(3)
+        );
+        // A server asking for a day's pause doesn't get to stall the download that long.
+        assert_eq!(
+            policy.delay(2, Some(Duration::from_secs(86400))),
+            Duration::from_secs(5)
+        );
+    }
+
+    #[test]
+    fn jitter_stays_within_bounds() {
+        let mut policy = RetryPolicy::default();
+        policy.with_base_delay(Duration::from_secs(4));
+
+        for _ in 0..100 {
+            let delay = policy.delay(1, None);
+            assert!(delay >= Duration::from_secs(2) && delay <= Duration::from_secs(4));
+        }
+    }
+
+    #[test]
+    fn budget_is_finite_by_default() {
+        let budget = RetryPolicy::default().budget();
+        for _ in 0..super::DEFAULT_RETRY_BUDGET {
+            assert!(budget.try_spend());
+        }
+        assert!(!budget.try_spend());
+    }
+
+    #[test]
+    fn budget_is_shared() {
+        let mut policy = RetryPolicy::default();
+        policy.with_budget(Some(2));
+        let budget = policy.budget();
+        let shared = budget.clone();
+
+        assert!(budget.try_spend());
+        assert!(shared.try_spend());
+        assert!(!budget.try_spend());
+    }
+
+    #[test]
+    fn retry_after_seconds_and_date() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+
+        let later = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(90));
+        let delay = parse_retry_after(&later).unwrap();
+        assert!(delay > Duration::from_secs(80) && delay <= Duration::from_secs(90));
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+}
@@ -0,0 +1,298 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::UNIX_EPOCH,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{verify, DownloadData, VerifyStatus};
+
+/// How a file that already exists at its output path is checked before it is skipped.
+///
+/// Files that fail the check are downloaded again and reported as repaired.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum SkipPolicy {
+    /// Trust any file of the expected size without hashing it.
+    #[default]
+    TrustSize,
+    /// Trust files whose size and modification time match an earlier verification recorded
+    /// in the given cache file; hash the others.
+    TrustSizeAndMtime(PathBuf),
+    /// Hash every existing file.
+    AlwaysHash,
+}
+
+/// State of a file found at the output path of a download.
+pub(crate) enum Existing {
+    Missing,
+    /// The file can be kept; contains how far it was verified.
+    Valid(VerifyStatus),
+    /// The file is there but has the wrong size or content.
+    Corrupted,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+struct CacheEntry {
+    size: u64,
+    mtime: u128,
+    sha1: String,
+}
+
+/// Files verified in earlier runs, keyed by path.
+#[derive(Default)]
+pub(crate) struct VerifyCache {
+    path: Option<PathBuf>,
+    entries: Mutex<HashMap<String, CacheEntry>>,
+}
+
+fn mtime(path: &Path) -> Option<(u64, u128)> {
+    let metadata = path.metadata().ok()?;
+    let mtime = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((metadata.len(), mtime.as_nanos()))
+}
+
+impl VerifyCache {
+    /// Loads the cache used by `policy`, if any. A missing or unreadable cache file is
+    /// treated as empty.
+    pub fn load(policy: &SkipPolicy) -> Self {
+        let SkipPolicy::TrustSizeAndMtime(path) = policy else {
+            return Self::default();
+        };
+        let entries = std::fs::read(path)
+            .ok()
+            .and_then(|raw| serde_json::from_slice(&raw).ok())
+            .unwrap_or_default();
+
+        Self {
+            path: Some(path.clone()),
+            entries: Mutex::new(entries),
+        }
+    }
+
+    fn get(&self, path: &Path, sha1: &str) -> bool {
+        let Some((size, mtime)) = mtime(path) else {
+            return false;
+        };
+        self.entries
+            .lock()
+            .unwrap()
+            .get(&path.to_string_lossy().to_string())
+            .is_some_and(|e| {
+                e.size == size && e.mtime == mtime && e.sha1.eq_ignore_ascii_case(sha1)
+            })
+    }
+
+    /// Records `path` as verified against `sha1` as it is now.
+    pub fn insert(&self, path: &Path, sha1: &str) {
+        if self.path.is_none() || sha1.is_empty() {
+            return;
+        }
+        if let Some((size, mtime)) = mtime(path) {
+            self.entries.lock().unwrap().insert(
+                path.to_string_lossy().to_string(),
+                CacheEntry {
+                    size,
+                    mtime,
+                    sha1: sha1.to_string(),
+                },
+            );
+        }
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let raw = serde_json::to_vec(&*self.entries.lock().unwrap())?;
+        std::fs::write(path, raw)
+    }
+}
+
+impl SkipPolicy {
+    /// Checks the file at `path` that `download` would produce.
+    ///
+    /// Hashing runs on the blocking pool, so other downloads keep going meanwhile.
+    pub(crate) async fn check(
+        &self,
+        path: &Path,
+        download: &DownloadData,
+        cache: &VerifyCache,
+    ) -> Existing {
+        let Ok(metadata) = path.metadata() else {
+            return Existing::Missing;
+        };
+        if !metadata.is_file() {
+            return Existing::Missing;
+        }
+        // A size of 0 means unknown, which leaves only the hash to check.
+        let known_size = download.total_size != 0;
+        if known_size && metadata.len() != download.total_size {
+            return Existing::Corrupted;
+        }
+        // Without a checksum the size is all there is to check; without either, the file
+        // can't be trusted but isn't known to be broken.
+        if download.sha1.is_empty() {
+            return if known_size {
+                Existing::Valid(VerifyStatus::NotVerified)
+            } else {
+                Existing::Missing
+            };
+        }
+
+        match self {
+            Self::TrustSize if known_size => Existing::Valid(VerifyStatus::NotVerified),
+            Self::TrustSizeAndMtime(_) if cache.get(path, &download.sha1) => {
+                Existing::Valid(VerifyStatus::Ok)
+            }
+            _ => {
+                let (sha1, file) = (download.sha1.clone(), path.to_path_buf());
+                let verified =
+                    tokio::task::spawn_blocking(move || verify::verify_file(&sha1, file))
+                        .await
+                        .unwrap_or(VerifyStatus::Failed);
+                match verified {
+                    VerifyStatus::Ok => {
+                        cache.insert(path, &download.sha1);
+                        Existing::Valid(VerifyStatus::Ok)
+                    }
+                    _ => Existing::Corrupted,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use crate::client::{
+        test_server::{sha1, temp_dir, TestServer},
+        DownloadData, DownloaderService, VerifyStatus,
+    };
+
+    use super::SkipPolicy;
+
+    fn run(dir: &std::path::Path, server: &TestServer, policy: SkipPolicy) -> (bool, VerifyStatus) {
+        let mut download = DownloadData::new(&format!("{}/a.bin", server.url()), "a.bin");
+        download.total_size = 8;
+        download.sha1 = sha1(b"original");
+
+        let output = DownloaderService::new(dir.to_path_buf())
+            .with_downloads(vec![download])
+            .with_skip_policy(policy)
+            .run(None)
+            .unwrap()
+            .remove(0)
+            .unwrap();
+        (output.repaired, output.verified)
+    }
+
+    #[test]
+    fn corrupted_file_of_right_size_is_repaired() {
+        let server = TestServer::start();
+        server.serve("/a.bin", b"original");
+        let dir = temp_dir("skip-hash");
+        std::fs::write(dir.join("a.bin"), b"corrupt!").unwrap();
+
+        assert_eq!(
+            run(&dir, &server, SkipPolicy::TrustSize),
+            (false, VerifyStatus::NotVerified)
+        );
+        assert!(server.requests("/a.bin").is_empty());
+
+        assert_eq!(
+            run(&dir, &server, SkipPolicy::AlwaysHash),
+            (true, VerifyStatus::Ok)
+        );
+        assert_eq!(std::fs::read(dir.join("a.bin")).unwrap(), b"original");
+
+        assert_eq!(
+            run(&dir, &server, SkipPolicy::AlwaysHash),
+            (false, VerifyStatus::Ok)
+        );
+        assert_eq!(server.requests("/a.bin").len(), 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn unknown_size_relies_on_the_hash() {
+        let server = TestServer::start();
+        server.serve("/a.bin", b"original");
+        let dir = temp_dir("skip-unknown-size");
+        std::fs::write(dir.join("a.bin"), b"original").unwrap();
+        let run = |sha1: String| {
+            let mut download = DownloadData::new(&format!("{}/a.bin", server.url()), "a.bin");
+            download.sha1 = sha1;
+            let output = DownloaderService::new(dir.clone())
+                .with_downloads(vec![download])
+                .run(None)
+                .unwrap()
+                .remove(0)
+                .unwrap();
+            (output.repaired, output.verified)
+        };
+
+        // Hashed even under `TrustSize`, and kept when it matches.
+        assert_eq!(run(sha1(b"original")), (false, VerifyStatus::Ok));
+        assert!(server.requests("/a.bin").is_empty());
+
+        // Nothing to check against: fetched again, but not reported as a repair.
+        assert!(!run(String::new()).0);
+        assert_eq!(server.requests("/a.bin").len(), 1);
+
+        std::fs::write(dir.join("a.bin"), b"corrupt").unwrap();
+        assert_eq!(run(sha1(b"original")), (true, VerifyStatus::Ok));
+        assert_eq!(std::fs::read(dir.join("a.bin")).unwrap(), b"original");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn cache_trusts_unchanged_size_and_mtime() {
+        let server = TestServer::start();
+        server.serve("/a.bin", b"original");
+        let dir = temp_dir("skip-cache");
+        let policy = SkipPolicy::TrustSizeAndMtime(dir.join("cache").join("verified.json"));
+
+        assert_eq!(
+            run(&dir, &server, policy.clone()),
+            (false, VerifyStatus::Ok)
+        );
+        assert!(dir.join("cache").join("verified.json").exists());
+
+        // Same size and mtime as recorded: trusted without hashing.
+        let path = dir.join("a.bin");
+        let mtime = path.metadata().unwrap().modified().unwrap();
+        std::fs::write(&path, b"corrupt!").unwrap();
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+        assert_eq!(
+            run(&dir, &server, policy.clone()),
+            (false, VerifyStatus::Ok)
+        );
+        assert_eq!(server.requests("/a.bin").len(), 1);
+
+        // A newer mtime invalidates the entry, so the file is hashed and repaired.
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(mtime + std::time::Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(run(&dir, &server, policy), (true, VerifyStatus::Ok));
+        assert_eq!(server.requests("/a.bin").len(), 2);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,273 @@
+//! A minimal HTTP/1.1 server for hermetic tests of the download pipeline.
+
+use sha1::{Digest, Sha1};
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+/// A fresh, empty directory for a test.
+pub(crate) fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mc_downloader_{name}_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Hex SHA-1 of `data`.
+pub(crate) fn sha1(data: &[u8]) -> String {
+    format!("{:x}", Sha1::digest(data))
+}
+
+/// A zip archive holding `entries`, as used for jars.
+pub(crate) fn zip(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for (name, body) in entries {
+        writer
+            .start_file(*name, zip::write::FileOptions::default())
+            .unwrap();
+        writer.write_all(body).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+/// Serves a Linux `java-runtime-gamma` release made of `files` (path, body, executable) and
+/// the raw manifest `entries`, returning the URL of its runtime index.
+pub(crate) fn serve_java_runtime(
+    server: &TestServer,
+    files: &[(&str, &[u8], bool)],
+    entries: &[&str],
+) -> String {
+    let url = server.url();
+    let files = files.iter().map(|(name, body, executable)| {
+        server.serve(&format!("/files/{name}"), body);
+        format!(
+            r#""{name}": {{"type": "file", "executable": {executable}, "downloads": {{"raw": {{"sha1": "{}", "size": {}, "url": "{url}/files/{name}"}}}}}}"#,
+            sha1(body),
+            body.len()
+        )
+    });
+    let manifest = format!(
+        r#"{{"files": {{{}}}}}"#,
+        files
+            .chain(entries.iter().map(|e| e.to_string()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let index = format!(
+        r#"{{"linux": {{"java-runtime-gamma": [{{
+            "availability": {{"group": 1, "progress": 100}},
+            "manifest": {{"sha1": "{}", "size": {}, "url": "{url}/manifest.json"}},
+            "version": {{"name": "17.0.8", "released": "2023-10-10T15:38:56+00:00"}}
+        }}]}}}}"#,
+        sha1(manifest.as_bytes()),
+        manifest.len()
+    );
+    server.serve("/manifest.json", manifest.as_bytes());
+    server.serve("/java-runtimes.json", index.as_bytes());
+    format!("{url}/java-runtimes.json")
+}
+
+/// A `.tar.gz` of a small JDK wrapped in `root/`, as vendors ship them.
+pub(crate) fn tar_gz(root: &str) -> Vec<u8> {
+    let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+        Vec::new(),
+        flate2::Compression::default(),
+    ));
+    let mut file = |path: &str, mode: u32, body: &[u8]| {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(body.len() as u64);
+        header.set_mode(mode);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, format!("{root}/{path}"), body)
+            .unwrap();
+    };
+    file("bin/java", 0o755, b"#!/bin/sh\n");
+    file("release", 0o644, b"JAVA_VERSION=\"17.0.8\"\n");
+    file("lib/libjli.so", 0o644, b"libjli");
+
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::Symlink);
+    header.set_size(0);
+    builder
+        .append_link(&mut header, format!("{root}/lib/libjli.so.1"), "libjli.so")
+        .unwrap();
+
+    builder.into_inner().unwrap().finish().unwrap()
+}
+
+/// A request as seen by a route handler.
+#[derive(Clone, Debug)]
+pub(crate) struct Request {
+    pub path: String,
+    pub headers: HashMap<String, String>,
+}
+
+/// A canned response.
+pub(crate) struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// Announce the full body length but close the connection after this many bytes.
+    pub cut_after: Option<usize>,
+}
+
+type Handler = Box<dyn Fn(&Request) -> Response + Send + Sync>;
+
+#[derive(Clone)]
+pub(crate) struct TestServer {
+    addr: String,
+    routes: Arc<Mutex<HashMap<String, Handler>>>,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl Response {
+    pub fn status(status: u16) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+            cut_after: None,
+        }
+    }
+
+    pub fn ok(body: &[u8]) -> Self {
+        Self {
+            body: body.to_vec(),
+            ..Self::status(200)
+        }
+    }
+
+    /// Serves `body`, honouring a `Range: bytes=N-` request header.
+    pub fn ranged(request: &Request, body: &[u8]) -> Self {
+        let start = request
+            .headers
+            .get("range")
+            .and_then(|r| r.strip_prefix("bytes="))
+            .and_then(|r| r.trim_end_matches('-').parse::<usize>().ok());
+
+        let mut response = match start {
+            Some(start) if start >= body.len() => Self::status(416),
+            Some(start) => {
+                let mut response = Self::ok(&body[start..]);
+                response.status = 206;
+                response.headers.push((
+                    "Content-Range".to_string(),
+                    format!("bytes {}-{}/{}", start, body.len() - 1, body.len()),
+                ));
+                response
+            }
+            None => Self::ok(body),
+        };
+        response
+            .headers
+            .push(("Accept-Ranges".to_string(), "bytes".to_string()));
+        response
+    }
+}
+
+impl TestServer {
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let server = Self {
+            addr: format!("http://{}", listener.local_addr().unwrap()),
+            routes: Default::default(),
+            requests: Default::default(),
+        };
+
+        let handle = server.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let handle = handle.clone();
+                std::thread::spawn(move || handle.handle(stream));
+            }
+        });
+
+        server
+    }
+
+    /// Base URL of the server, without a trailing slash.
+    pub fn url(&self) -> &str {
+        &self.addr
+    }
+
+    /// Serves `body` under `path`, with range support.
+    pub fn serve(&self, path: &str, body: &[u8]) {
+        let body = body.to_vec();
+        self.route(path, move |request| Response::ranged(request, &body));
+    }
+
+    pub fn route(
+        &self,
+        path: &str,
+        handler: impl Fn(&Request) -> Response + Send + Sync + 'static,
+    ) {
+        self.routes
+            .lock()
+            .unwrap()
+            .insert(path.to_string(), Box::new(handler));
+    }
+
+    /// Every request received so far for `path`.
+    pub fn requests(&self, path: &str) -> Vec<Request> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|r| r.path == path)
+            .cloned()
+            .collect()
+    }
+
+    fn handle(&self, stream: TcpStream) {
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        if reader.read_line(&mut line).is_err() {
+            return;
+        }
+        let path = line.split_whitespace().nth(1).unwrap_or("/").to_string();
+
+        let mut headers = HashMap::new();
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header).is_err() || header.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+            }
+        }
+
+        let request = Request { path, headers };
+        self.requests.lock().unwrap().push(request.clone());
+
+        let response = match self.routes.lock().unwrap().get(&request.path) {
+            Some(handler) => handler(&request),
+            None => Response::status(404),
+        };
+
+        let mut stream = reader.into_inner();
+        let mut head = format!(
+            "HTTP/1.1 {} Test\r\nContent-Length: {}\r\nConnection: close\r\n",
+            response.status,
+            response.body.len()
+        );
+        for (name, value) in &response.headers {
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+        head.push_str("\r\n");
+
+        let body = match response.cut_after {
+            Some(cut) => &response.body[..cut.min(response.body.len())],
+            None => &response.body[..],
+        };
+        let _ = stream.write_all(head.as_bytes());
+        let _ = stream.write_all(body);
+        let _ = stream.flush();
+    }
+}
@@ -1,6 +1,6 @@
-use std::path::PathBuf;
+use std::{io::Read, path::PathBuf};
 
-use chksum::{hash::sha1::Digest, prelude::HashDigest, Chksum};
+use sha1::{Digest, Sha1};
 
 #[derive(Clone, Default, Debug, Eq, PartialEq)]
 pub enum VerifyStatus {
@@ -28,21 +28,56 @@ impl std::fmt::Display for VerifyStatus {
 }
 
 pub fn verify_file(hash: &str, path: PathBuf) -> VerifyStatus {
-    if let Ok(mut file) = std::fs::OpenOptions::new().read(true).open(&path) {
-        return match file.chksum(chksum::prelude::HashAlgorithm::SHA1) {
-            Ok(digest) => match Digest::try_from(hash) {
-                Ok(expected) => {
-                    if digest == HashDigest::SHA1(expected) {
-                        VerifyStatus::Ok
-                    } else {
-                        VerifyStatus::Failed
-                    }
-                }
-                Err(_) => VerifyStatus::Failed,
-            },
-            Err(_) => VerifyStatus::Failed,
-        };
+    match StreamVerifier::resume_from(&path, u64::MAX) {
+        Ok(verifier) => verifier.verify(hash),
+        Err(_) => VerifyStatus::Failed,
+    }
+}
+
+/// Computes the SHA-1 of a file from the chunks written to it, so it doesn't have to be read
+/// back once complete.
+#[derive(Clone, Default)]
+pub(crate) struct StreamVerifier(Sha1);
+
+impl StreamVerifier {
+    /// Starts from the first `len` bytes already in `path`, e.g. a resumed partial file.
+    pub fn resume_from(path: &std::path::Path, len: u64) -> std::io::Result<Self> {
+        let mut verifier = Self::default();
+        let mut file = std::fs::File::open(path)?.take(len);
+        std::io::copy(&mut file, &mut verifier.0)?;
+        Ok(verifier)
     }
 
-    VerifyStatus::Failed
+    pub fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    pub fn verify(self, hash: &str) -> VerifyStatus {
+        if format!("{:x}", self.0.finalize()).eq_ignore_ascii_case(hash) {
+            VerifyStatus::Ok
+        } else {
+            VerifyStatus::Failed
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StreamVerifier, VerifyStatus};
+
+    #[test]
+    fn stream_verifier_matches_across_chunks() {
+        let data = (0..1000).map(|i| (i % 256) as u8).collect::<Vec<u8>>();
+        let hash = crate::client::test_server::sha1(&data);
+
+        let mut verifier = StreamVerifier::default();
+        for chunk in [&data[..32], &data[32..64], &data[64..74], &data[74..]] {
+            verifier.update(chunk);
+        }
+        assert_eq!(verifier.verify(&hash), VerifyStatus::Ok);
+
+        let mut verifier = StreamVerifier::default();
+        verifier.update(&data[1..]);
+        assert_eq!(verifier.verify(&hash), VerifyStatus::Failed);
+    }
 }
@@ -0,0 +1,244 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Frames kept from the top of a stack trace.
+const STACK_TOP: usize = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CrashKind {
+    /// A report the game wrote to `crash-reports/`.
+    CrashReport,
+    /// An `hs_err_pid*.log` the JVM wrote when it died.
+    JvmFatalError,
+}
+
+/// The gist of why the game crashed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CrashSummary {
+    pub kind: CrashKind,
+    pub path: PathBuf,
+    pub description: String,
+    /// The exception, or for JVM errors the signal, that ended the game.
+    pub exception: Option<String>,
+    /// The topmost frames of the stack trace, or the problematic native frame.
+    pub stack_top: Vec<String>,
+    /// Mods listed in the report, if the game was modded.
+    pub mods: Vec<String>,
+}
+
+impl CrashSummary {
+    /// Parses the text of a `crash-reports/*.txt` file.
+    pub fn from_crash_report(path: PathBuf, text: &str) -> Self {
+        let mut lines = text.lines().skip_while(|l| !l.starts_with("Description:"));
+        let description = lines
+            .next()
+            .and_then(|l| l.strip_prefix("Description:"))
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        let mut lines = lines.skip_while(|l| l.trim().is_empty());
+        let exception = lines.next().map(|l| l.trim().to_string());
+        let stack_top = lines
+            .take_while(|l| l.trim_start().starts_with("at "))
+            .take(STACK_TOP)
+            .map(|l| l.trim().to_string())
+            .collect();
+
+        Self {
+            kind: CrashKind::CrashReport,
+            path,
+            description,
+            exception,
+            stack_top,
+            mods: mods(text),
+        }
+    }
+
+    /// Parses the header of an `hs_err_pid*.log` file.
+    pub fn from_jvm_log(path: PathBuf, text: &str) -> Self {
+        let header = text
+            .lines()
+            .take_while(|l| l.starts_with('#'))
+            .map(|l| l.trim_start_matches('#').trim())
+            .collect::<Vec<_>>();
+        let description = header
+            .iter()
+            .find(|l| !l.is_empty())
+            .map(|l| l.trim_end_matches(':').to_string())
+            .unwrap_or_default();
+        let exception = header
+            .iter()
+            .skip_while(|l| !l.starts_with("A fatal error"))
+            .skip(1)
+            .find(|l| !l.is_empty())
+            .map(|l| l.to_string());
+        let stack_top = header
+            .iter()
+            .skip_while(|l| !l.starts_with("Problematic frame:"))
+            .skip(1)
+            .take_while(|l| !l.is_empty())
+            .map(|l| l.to_string())
+            .collect();
+
+        Self {
+            kind: CrashKind::JvmFatalError,
+            path,
+            description,
+            exception,
+            stack_top,
+            mods: Vec::new(),
+        }
+    }
+}
+
+/// The entries of a `... Mods:` or `Mod List:` section in the system details.
+fn mods(text: &str) -> Vec<String> {
+    let indent = |l: &str| l.len() - l.trim_start().len();
+    let mut lines = text.lines();
+    let Some(header) = lines.find(|l| {
+        let l = l.trim();
+        l.ends_with("Mods:") || l.starts_with("Mod List:")
+    }) else {
+        return Vec::new();
+    };
+
+    lines
+        .take_while(|l| !l.trim().is_empty() && indent(l) > indent(header))
+        .map(|l| l.trim().to_string())
+        .collect()
+}
+
+/// Finds the newest crash report in `<game_path>/crash-reports/` or JVM error log in
+/// `game_path` written since `since`, usually the time the game was launched.
+pub fn find_crash(game_path: &Path, since: SystemTime) -> io::Result<Option<CrashSummary>> {
+    let mut candidates = Vec::new();
+    let mut collect = |dir: &Path, kind: CrashKind| -> io::Result<()> {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let matches = match kind {
+                CrashKind::CrashReport => name.ends_with(".txt"),
+                CrashKind::JvmFatalError => {
+                    name.starts_with("hs_err_pid") && name.ends_with(".log")
+                }
+            };
+            let modified = entry.metadata()?.modified()?;
+            if matches && modified >= since {
+                candidates.push((modified, entry.path(), kind));
+            }
+        }
+        Ok(())
+    };
+    collect(&game_path.join("crash-reports"), CrashKind::CrashReport)?;
+    collect(game_path, CrashKind::JvmFatalError)?;
+
+    let Some((_, path, kind)) = candidates
+        .into_iter()
+        .max_by_key(|(modified, ..)| *modified)
+    else {
+        return Ok(None);
+    };
+    let text = String::from_utf8_lossy(&fs::read(&path)?).to_string();
+    Ok(Some(match kind {
+        CrashKind::CrashReport => CrashSummary::from_crash_report(path, &text),
+        CrashKind::JvmFatalError => CrashSummary::from_jvm_log(path, &text),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::File,
+        path::PathBuf,
+        time::{Duration, SystemTime},
+    };
+
+    use crate::client::test_server::temp_dir;
+
+    use super::{find_crash, CrashKind, CrashSummary};
+
+    const REPORT: &str = include_str!("../tests/fixtures/crash/crash-client.txt");
+    const JVM_LOG: &str = include_str!("../tests/fixtures/crash/hs_err_pid4242.log");
+
+    #[test]
+    fn crash_report_header_and_mods() {
+        let summary = CrashSummary::from_crash_report(PathBuf::from("crash.txt"), REPORT);
+
+        assert_eq!(summary.description, "Unexpected error");
+        assert!(summary
+            .exception
+            .as_deref()
+            .unwrap()
+            .starts_with("java.lang.NullPointerException: Cannot invoke"));
+        assert_eq!(summary.stack_top.len(), 4);
+        assert_eq!(
+            summary.stack_top[0],
+            "at net.minecraft.client.Minecraft.tick(Minecraft.java:1845)"
+        );
+        assert_eq!(
+            summary.mods,
+            [
+                "fabric-api: Fabric API 0.97.0+1.20.4",
+                "fabricloader: Fabric Loader 0.15.9",
+                "sodium: Sodium 0.5.8"
+            ]
+        );
+    }
+
+    #[test]
+    fn jvm_log_header() {
+        let summary = CrashSummary::from_jvm_log(PathBuf::from("hs_err_pid4242.log"), JVM_LOG);
+
+        assert_eq!(
+            summary.description,
+            "A fatal error has been detected by the Java Runtime Environment"
+        );
+        assert_eq!(
+            summary.exception.as_deref(),
+            Some("SIGSEGV (0xb) at pc=0x00007f3c2d1a2b3c, pid=4242, tid=4243")
+        );
+        assert_eq!(
+            summary.stack_top,
+            ["C  [liblwjgl_opengl.so+0x1b3c]  Java_org_lwjgl_opengl_GL11C_glDrawElements+0x1c"]
+        );
+        assert!(summary.mods.is_empty());
+    }
+
+    #[test]
+    fn newest_crash_since_launch() {
+        let dir = temp_dir("crash");
+        let launched = SystemTime::now() - Duration::from_secs(60);
+        assert!(find_crash(&dir, launched).unwrap().is_none());
+
+        std::fs::create_dir_all(dir.join("crash-reports")).unwrap();
+        let old = dir.join("crash-reports/crash-old-client.txt");
+        std::fs::write(&old, REPORT).unwrap();
+        File::options()
+            .write(true)
+            .open(&old)
+            .unwrap()
+            .set_modified(launched - Duration::from_secs(60))
+            .unwrap();
+        assert!(find_crash(&dir, launched).unwrap().is_none());
+
+        std::fs::write(dir.join("crash-reports/crash-new-client.txt"), REPORT).unwrap();
+        let summary = find_crash(&dir, launched).unwrap().unwrap();
+        assert_eq!(summary.kind, CrashKind::CrashReport);
+        assert!(summary.path.ends_with("crash-new-client.txt"));
+
+        std::thread::sleep(Duration::from_millis(20));
+        std::fs::write(dir.join("hs_err_pid4242.log"), JVM_LOG).unwrap();
+        let summary = find_crash(&dir, launched).unwrap().unwrap();
+        assert_eq!(summary.kind, CrashKind::JvmFatalError);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -13,6 +13,12 @@ pub enum ClientDownloaderError {
     #[error("No such directory")]
     NoSuchDirectory,
 
+    #[error("No Java runtime `{0}` for this platform")]
+    NoSuchRuntime(String),
+
+    #[error("Archive entry `{0}` would be written outside its directory")]
+    UnsafeArchiveEntry(String),
+
     #[error("{0}")]
     Request(#[from] reqwest::Error),
 
@@ -24,6 +30,12 @@ pub enum ClientDownloaderError {
 
     #[error("{0}")]
     Download(#[from] DownloadError),
+
+    #[error("{0}")]
+    Zip(#[from] zip::result::ZipError),
+
+    #[error("{0}")]
+    Manifest(#[from] ManifestError),
 }
 
 #[derive(Error, Debug)]
@@ -42,9 +54,50 @@ pub enum ManifestError {
 
     #[error("{0}")]
     Json(#[from] serde_json::Error),
+
+    #[error("Missing field `{0}`")]
+    MissingField(String),
+
+    #[error("Asset `{name}` has an invalid hash {hash:?}")]
+    InvalidAssetHash { name: String, hash: String },
+
+    #[error("Asset `{name}` has an invalid size {size}")]
+    InvalidAssetSize { name: String, size: String },
 }
 
 #[derive(Error, Debug)]
+pub enum JavaDownloadError {
+    #[error("{0}")]
+    Download(#[from] DownloadError),
+
+    #[error("No Java {version} for {os}-{arch}")]
+    NotFound {
+        version: String,
+        os: String,
+        arch: String,
+    },
+
+    #[error("No Java builds for {os}-{arch}")]
+    UnsupportedPlatform { os: String, arch: String },
+
+    #[error("No published checksum for {0}")]
+    MissingChecksum(String),
+
+    #[error("Checksum mismatch for {url}: expected {expected}, got {actual}")]
+    ChecksumMismatch {
+        url: String,
+        expected: String,
+        actual: String,
+    },
+
+    #[error("{0}")]
+    IO(#[from] std::io::Error),
+
+    #[error("{0}")]
+    Install(#[from] ClientDownloaderError),
+}
+
+#[derive(Error, Debug, Clone)]
 pub enum DownloadError {
     /// The Setup is incomplete or bogus.
     #[error("Setup error: {0}")]
@@ -61,4 +114,11 @@ pub enum DownloadError {
     /// Download file verification failed.
     #[error("Verification failed for {0}")]
     Verification(DownloadOutput),
+    /// The job was cancelled before the file was complete.
+    #[error("Download cancelled for {0}")]
+    Cancelled(DownloadOutput),
+    /// The verified file was moved into place, but syncing its directory failed, so it may
+    /// not survive a crash.
+    #[error("Could not persist {0}")]
+    Persist(DownloadOutput),
 }
@@ -0,0 +1,535 @@
+use std::{collections::HashMap, io, path::PathBuf, process::Command};
+
+use crate::{
+    asset_index::{read_asset_index_from_str, AssetIndex},
+    manifest::{Manifest, ManifestLibrary, VersionType},
+    process::GameProcess,
+    rules::RuleEnvironment,
+};
+
+/// The player the game is started for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Account {
+    pub name: String,
+    /// UUID without dashes.
+    pub uuid: String,
+    pub access_token: String,
+    /// `msa` for Microsoft accounts, `legacy` for offline play.
+    pub user_type: String,
+    pub xuid: String,
+    pub client_id: String,
+    /// Starts the game in demo mode.
+    pub demo: bool,
+}
+
+impl Default for Account {
+    fn default() -> Self {
+        Self::offline("Player")
+    }
+}
+
+impl Account {
+    /// An account for offline play.
+    pub fn offline(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            uuid: "0".repeat(32),
+            access_token: "0".to_string(),
+            user_type: "legacy".to_string(),
+            xuid: String::new(),
+            client_id: String::new(),
+            demo: false,
+        }
+    }
+}
+
+/// Builds the command line starting a version, from its manifest and the files installed by
+/// [`crate::client::AsyncDownloadVersion`].
+///
+/// Placeholders the builder knows nothing about are left as they are.
+#[derive(Clone, Debug)]
+pub struct LaunchCommand {
+    manifest: Manifest,
+    game_path: PathBuf,
+    java: PathBuf,
+    account: Account,
+    environment: RuleEnvironment,
+    version_path: Option<PathBuf>,
+    natives_path: Option<PathBuf>,
+    game_assets: Option<PathBuf>,
+    min_memory: Option<u32>,
+    max_memory: Option<u32>,
+    resolution: Option<(u32, u32)>,
+    jvm_args: Vec<String>,
+    logging: bool,
+    launcher: (String, String),
+}
+
+impl LaunchCommand {
+    pub fn new(manifest: Manifest, game_path: PathBuf, java: PathBuf) -> Self {
+        Self {
+            manifest,
+            game_path,
+            java,
+            account: Account::default(),
+            environment: RuleEnvironment::default(),
+            version_path: None,
+            natives_path: None,
+            game_assets: None,
+            min_memory: None,
+            max_memory: None,
+            resolution: None,
+            jvm_args: Vec::new(),
+            logging: true,
+            launcher: (
+                env!("CARGO_PKG_NAME").to_string(),
+                env!("CARGO_PKG_VERSION").to_string(),
+            ),
+        }
+    }
+
+    pub fn with_account(&mut self, account: Account) -> &mut Self {
+        self.account = account;
+        self
+    }
+
+    /// Sets the platform and features rules are evaluated against.
+    pub fn with_environment(&mut self, environment: RuleEnvironment) -> &mut Self {
+        self.environment = environment;
+        self
+    }
+
+    /// Overrides the client jar, by default `versions/<id>/<id>.jar` in the game directory.
+    pub fn with_version_path(&mut self, version_path: PathBuf) -> &mut Self {
+        self.version_path = Some(version_path);
+        self
+    }
+
+    /// Overrides the natives directory, by default `versions/<id>/natives`.
+    pub fn with_natives_path(&mut self, natives_path: PathBuf) -> &mut Self {
+        self.natives_path = Some(natives_path);
+        self
+    }
+
+    /// Overrides `${game_assets}`, by default the legacy directory of the installed asset
+    /// index, see [`AssetIndex::legacy_path`], or the `assets` directory for current indexes.
+    pub fn with_game_assets(&mut self, game_assets: PathBuf) -> &mut Self {
+        self.game_assets = Some(game_assets);
+        self
+    }
+
+    /// Initial and maximum heap size in MiB.
+    pub fn with_memory(&mut self, min: Option<u32>, max: Option<u32>) -> &mut Self {
+        self.min_memory = min;
+        self.max_memory = max;
+        self
+    }
+
+    pub fn with_resolution(&mut self, width: u32, height: u32) -> &mut Self {
+        self.resolution = Some((width, height));
+        self
+    }
+
+    /// Extra JVM arguments, added after the ones of the version.
+    pub fn with_jvm_args(&mut self, jvm_args: Vec<String>) -> &mut Self {
+        self.jvm_args = jvm_args;
+        self
+    }
+
+    /// Whether to pass the logging configuration of the version, which makes the game log
+    /// XML events that [`crate::process::GameProcess`] parses. On by default.
+    pub fn with_logging(&mut self, logging: bool) -> &mut Self {
+        self.logging = logging;
+        self
+    }
+
+    /// Name and version reported to the game as `launcher_name` and `launcher_version`.
+    pub fn with_launcher(&mut self, name: &str, version: &str) -> &mut Self {
+        self.launcher = (name.to_string(), version.to_string());
+        self
+    }
+
+    fn version_dir(&self) -> PathBuf {
+        self.game_path.join("versions").join(&self.manifest.id)
+    }
+
+    fn version_path(&self) -> PathBuf {
+        self.version_path
+            .clone()
+            .unwrap_or_else(|| self.version_dir().join(format!("{}.jar", self.manifest.id)))
+    }
+
+    fn natives_path(&self) -> PathBuf {
+        self.natives_path
+            .clone()
+            .unwrap_or_else(|| self.version_dir().join("natives"))
+    }
+
+    fn game_assets(&self) -> PathBuf {
+        if let Some(game_assets) = &self.game_assets {
+            return game_assets.clone();
+        }
+        let id = &self.manifest.asset_index.id;
+        let installed = self
+            .game_path
+            .join("assets")
+            .join("indexes")
+            .join(format!("{id}.json"));
+        let index = std::fs::read_to_string(installed)
+            .ok()
+            .and_then(|raw| read_asset_index_from_str(&raw).ok())
+            // Not installed yet: the two legacy indexes are known by their ids.
+            .unwrap_or_else(|| AssetIndex {
+                virtual_: id == "legacy",
+                map_to_resources: id == "pre-1.6",
+                ..Default::default()
+            });
+        index
+            .legacy_path(&self.game_path, id)
+            .unwrap_or_else(|| self.game_path.join("assets"))
+    }
+
+    fn classpath_separator(&self) -> &'static str {
+        if self.environment.platform.os == "windows" {
+            ";"
+        } else {
+            ":"
+        }
+    }
+
+    /// The libraries allowed in the environment, followed by the client jar.
+    pub fn classpath(&self) -> Vec<PathBuf> {
+        let libraries = self.game_path.join("libraries");
+        self.manifest
+            .libraries
+            .iter()
+            .filter(|l| l.is_allowed(&self.environment))
+            .filter_map(library_path)
+            .map(|path| libraries.join(path))
+            .chain(std::iter::once(self.version_path()))
+            .collect()
+    }
+
+    fn environment(&self) -> RuleEnvironment {
+        let mut environment = self.environment.clone();
+        environment
+            .with_feature("is_demo_user", self.account.demo)
+            .with_feature("has_custom_resolution", self.resolution.is_some());
+        environment
+    }
+
+    fn placeholders(&self) -> HashMap<&'static str, String> {
+        let display = |path: PathBuf| path.to_string_lossy().to_string();
+        let assets = display(self.game_path.join("assets"));
+        let classpath = self
+            .classpath()
+            .into_iter()
+            .map(display)
+            .collect::<Vec<_>>()
+            .join(self.classpath_separator());
+        let version_type = match self.manifest.type_ {
+            VersionType::Release => "release",
+            VersionType::Snapshot => "snapshot",
+            VersionType::OldBeta => "old_beta",
+            VersionType::OldAlpha => "old_alpha",
+        };
+        let (width, height) = self.resolution.unwrap_or_default();
+        let account = &self.account;
+
+        HashMap::from([
+            ("auth_player_name", account.name.clone()),
+            ("auth_uuid", account.uuid.clone()),
+            ("auth_access_token", account.access_token.clone()),
+            (
+                "auth_session",
+                format!("token:{}:{}", account.access_token, account.uuid),
+            ),
+            ("auth_xuid", account.xuid.clone()),
+            ("clientid", account.client_id.clone()),
+            ("user_type", account.user_type.clone()),
+            ("user_properties", "{}".to_string()),
+            ("version_name", self.manifest.id.clone()),
+            ("version_type", version_type.to_string()),
+            ("game_directory", display(self.game_path.clone())),
+            ("assets_root", assets.clone()),
+            ("game_assets", display(self.game_assets())),
+            ("assets_index_name", self.manifest.asset_index.id.clone()),
+            ("natives_directory", display(self.natives_path())),
+            (
+                "library_directory",
+                display(self.game_path.join("libraries")),
+            ),
+            ("classpath", classpath),
+            (
+                "classpath_separator",
+                self.classpath_separator().to_string(),
+            ),
+            ("launcher_name", self.launcher.0.clone()),
+            ("launcher_version", self.launcher.1.clone()),
+            ("resolution_width", width.to_string()),
+            ("resolution_height", height.to_string()),
+        ])
+    }
+
+    /// The full argv, starting with the Java executable.
+    pub fn build(&self) -> Vec<String> {
+        let environment = self.environment();
+        let placeholders = self.placeholders();
+        let substitute = |arg: &str| substitute(arg, &placeholders);
+
+        let mut argv = vec![self.java.to_string_lossy().to_string()];
+
+        match &self.manifest.arguments {
+            Some(arguments) => argv.extend(
+                arguments
+                    .jvm_arguments(&environment)
+                    .into_iter()
+                    .map(substitute),
+            ),
+            // Versions before 1.13 leave the JVM arguments to the launcher.
+            None => argv.extend(
+                [
+                    "-Djava.library.path=${natives_directory}",
+                    "-cp",
+                    "${classpath}",
+                ]
+                .map(substitute),
+            ),
+        }
+        if let Some(min) = self.min_memory {
+            argv.push(format!("-Xms{min}M"));
+        }
+        if let Some(max) = self.max_memory {
+            argv.push(format!("-Xmx{max}M"));
+        }
+        argv.extend(self.jvm_args.iter().cloned());
+        if self.logging {
+            if let Some(logging) = self
+                .manifest
+                .logging
+                .as_ref()
+                .and_then(|l| l.client.as_ref())
+            {
+                argv.push(logging.jvm_argument(&self.game_path));
+            }
+        }
+
+        argv.push(self.manifest.main_class.clone());
+
+        match (&self.manifest.arguments, &self.manifest.minecraft_arguments) {
+            (Some(arguments), _) => argv.extend(
+                arguments
+                    .game_arguments(&environment)
+                    .into_iter()
+                    .map(substitute),
+            ),
+            (None, Some(legacy)) => {
+                argv.extend(legacy.split_whitespace().map(substitute));
+                if self.account.demo {
+                    argv.push("--demo".to_string());
+                }
+                if let Some((width, height)) = self.resolution {
+                    argv.extend([
+                        "--width".to_string(),
+                        width.to_string(),
+                        "--height".to_string(),
+                        height.to_string(),
+                    ]);
+                }
+            }
+            (None, None) => {}
+        }
+
+        argv
+    }
+
+    /// A [`Command`] running [`Self::build`] in the game directory.
+    pub fn command(&self) -> Command {
+        let argv = self.build();
+        let mut command = Command::new(&argv[0]);
+        command.args(&argv[1..]).current_dir(&self.game_path);
+        command
+    }
+
+    /// Starts the game, see [`GameProcess`]. Must be called within a Tokio runtime.
+    pub fn spawn(&self) -> io::Result<GameProcess> {
+        GameProcess::spawn(self.command())
+    }
+}
+
+/// Path of a library jar relative to the libraries directory, from its download or, for
+/// libraries that only name a maven artifact, from its coordinates.
+fn library_path(library: &ManifestLibrary) -> Option<PathBuf> {
+    if let Some(artifact) = &library.downloads.artifact {
+        return artifact.path.as_ref().map(PathBuf::from);
+    }
+    // Natives-only libraries have nothing to put on the classpath.
+    if library.natives.is_some() || library.downloads.classifiers.is_some() {
+        return None;
+    }
+
+    let mut parts = library.name.split(':');
+    let (group, artifact, version) = (parts.next()?, parts.next()?, parts.next()?);
+    let file = match parts.next() {
+        Some(classifier) => format!("{artifact}-{version}-{classifier}.jar"),
+        None => format!("{artifact}-{version}.jar"),
+    };
+    let mut path = group.split('.').collect::<PathBuf>();
+    path.extend([artifact, version, &file]);
+    Some(path)
+}
+
+/// Replaces every known `${name}` in `arg`.
+fn substitute(arg: &str, placeholders: &HashMap<&'static str, String>) -> String {
+    let mut result = String::with_capacity(arg.len());
+    let mut rest = arg;
+    while let Some(start) = rest.find("${") {
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        result.push_str(&rest[..start]);
+        let name = &rest[start + 2..start + end];
+        match placeholders.get(name) {
+            Some(value) => result.push_str(value),
+            None => result.push_str(&rest[start..=start + end]),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::{
+        client::test_server::temp_dir,
+        manifest::read_manifest_from_str,
+        rules::{Platform, RuleEnvironment},
+    };
+
+    use super::{Account, LaunchCommand};
+
+    const OLD_ALPHA: &str = include_str!("../tests/fixtures/versions/a1.0.4.json");
+    const LEGACY: &str = include_str!("../tests/fixtures/versions/1.12.2.json");
+    const ARGUMENTS: &str = include_str!("../tests/fixtures/versions/1.13.json");
+    const LEGACY_ARGV: &str = include_str!("../tests/fixtures/launch/1.12.2-linux.json");
+    const ARGUMENTS_ARGV: &str = include_str!("../tests/fixtures/launch/1.13-windows.json");
+
+    fn argv(fixture: &str) -> Vec<String> {
+        serde_json::from_str(fixture).unwrap()
+    }
+
+    fn account() -> Account {
+        Account {
+            uuid: "069a79f444e94726a5befca90e38aaf5".to_string(),
+            access_token: "token".to_string(),
+            user_type: "msa".to_string(),
+            ..Account::offline("Notch")
+        }
+    }
+
+    #[test]
+    fn legacy_arguments() {
+        let manifest = read_manifest_from_str(LEGACY).unwrap();
+        let mut command = LaunchCommand::new(
+            manifest,
+            PathBuf::from("/game"),
+            PathBuf::from("/usr/bin/java"),
+        );
+        command
+            .with_account(account())
+            .with_environment(RuleEnvironment::new(Platform::new("linux", "x86_64")))
+            .with_memory(Some(512), Some(2048))
+            .with_resolution(854, 480);
+
+        assert_eq!(command.build(), argv(LEGACY_ARGV));
+    }
+
+    #[test]
+    fn modern_arguments_with_rules() {
+        let manifest = read_manifest_from_str(ARGUMENTS).unwrap();
+        let mut windows = Platform::new("windows", "x86_64");
+        windows.with_version("10.0");
+        let mut command = LaunchCommand::new(
+            manifest,
+            PathBuf::from("/game"),
+            PathBuf::from("/java/bin/javaw"),
+        );
+        command
+            .with_account(Account {
+                demo: true,
+                ..account()
+            })
+            .with_environment(RuleEnvironment::new(windows))
+            .with_jvm_args(vec!["-XX:+UseG1GC".to_string()]);
+
+        assert_eq!(command.build(), argv(ARGUMENTS_ARGV));
+    }
+
+    #[test]
+    fn maven_coordinates_and_unknown_placeholders() {
+        let mut raw: serde_json::Value = serde_json::from_str(ARGUMENTS).unwrap();
+        raw["libraries"] = serde_json::json!([{
+            "name": "net.fabricmc:fabric-loader:0.15.9",
+            "url": "https://maven.fabricmc.net/"
+        }]);
+        raw["arguments"]["game"] = serde_json::json!(["${unknown}", "--dir=${game_directory}/x"]);
+        let manifest = read_manifest_from_str(&raw.to_string()).unwrap();
+
+        let command = LaunchCommand::new(manifest, PathBuf::from("/game"), PathBuf::from("java"));
+        assert_eq!(
+            command.classpath(),
+            [
+                PathBuf::from(
+                    "/game/libraries/net/fabricmc/fabric-loader/0.15.9/fabric-loader-0.15.9.jar"
+                ),
+                PathBuf::from("/game/versions/1.13/1.13.jar"),
+            ]
+        );
+        assert!(command
+            .build()
+            .ends_with(&["${unknown}".to_string(), "--dir=/game/x".to_string()]));
+    }
+
+    #[test]
+    fn legacy_game_assets() {
+        let manifest = read_manifest_from_str(OLD_ALPHA).unwrap();
+        let command = LaunchCommand::new(manifest, PathBuf::from("/game"), PathBuf::from("java"));
+
+        let argv = command.build();
+        let assets = argv.iter().position(|a| a == "--assetsDir").unwrap();
+        assert_eq!(argv[assets + 1], "/game/resources");
+    }
+
+    #[test]
+    fn game_assets_follow_installed_index() {
+        let dir = temp_dir("launch-assets");
+        let mut raw: serde_json::Value = serde_json::from_str(LEGACY).unwrap();
+        raw["minecraftArguments"] = "--assetsDir ${game_assets}".into();
+        let manifest = read_manifest_from_str(&raw.to_string()).unwrap();
+        let game_assets = || {
+            LaunchCommand::new(manifest.clone(), dir.clone(), PathBuf::from("java"))
+                .build()
+                .pop()
+                .unwrap()
+        };
+
+        assert_eq!(game_assets(), dir.join("assets").to_str().unwrap());
+
+        let indexes = dir.join("assets").join("indexes");
+        std::fs::create_dir_all(&indexes).unwrap();
+        std::fs::write(
+            indexes.join("1.12.json"),
+            r#"{"virtual": true, "objects": {}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            game_assets(),
+            dir.join("assets/virtual/1.12").to_str().unwrap()
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
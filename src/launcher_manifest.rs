@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct LauncherManifestLatest {
     pub release: String,
     pub snapshot: String,
@@ -17,7 +17,7 @@ pub struct LauncherManifestVersion {
     pub version_type: String,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct LauncherManifest {
     pub latest: LauncherManifestLatest,
     pub versions: Vec<LauncherManifestVersion>,
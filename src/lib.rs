@@ -1,10 +1,20 @@
+pub mod asset_index;
 pub mod client;
+pub mod crash;
 pub mod error;
+pub mod launch;
 pub mod launcher_manifest;
 pub mod manifest;
+pub mod process;
+pub mod rules;
 
 pub mod prelude {
+    pub use super::asset_index::*;
     pub use super::client::*;
+    pub use super::crash::*;
     pub use super::error::*;
+    pub use super::launch::*;
     pub use super::manifest::*;
+    pub use super::process::*;
+    pub use super::rules::*;
 }
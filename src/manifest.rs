@@ -1,28 +1,31 @@
-use std::{collections::HashMap, fs};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
 
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 use crate::error::ManifestError;
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 #[serde(rename_all(deserialize = "camelCase"))]
 pub struct ManifestAssetIndex {
     pub id: String,
     pub sha1: String,
-    pub size: i32,
-    pub total_size: i32,
+    pub size: u64,
+    pub total_size: u64,
     pub url: String,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 #[serde(rename_all(deserialize = "camelCase"))]
 pub struct ManifestComponent {
     pub component: String,
     pub major_version: i8,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 #[serde(rename_all(deserialize = "camelCase"))]
 pub struct ManifestFile {
     pub path: Option<String>,
@@ -31,34 +34,138 @@ pub struct ManifestFile {
     pub url: String,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct ManifestDownloads {
     pub client: ManifestFile,
     pub client_mappings: Option<ManifestFile>,
-    pub server: ManifestFile,
+    /// Missing for the earliest alpha versions, which had no server.
+    pub server: Option<ManifestFile>,
     pub server_mappings: Option<ManifestFile>,
+    /// Only shipped by a few old versions.
+    pub windows_server: Option<ManifestFile>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleAction {
+    Allow,
+    Disallow,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+/// The operating system a rule matches. Every present field has to match.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ManifestRuleOs {
+    /// `windows`, `osx` or `linux`.
+    pub name: Option<String>,
+    /// A regular expression matched against the OS version.
+    pub version: Option<String>,
+    /// `x86` or `x86_64` style architecture name.
+    pub arch: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all(deserialize = "camelCase"))]
 pub struct ManifestRule {
-    pub action: String,
-    pub os: Option<HashMap<String, String>>,
-    pub features: Option<HashMap<String, Value>>,
+    pub action: RuleAction,
+    pub os: Option<ManifestRuleOs>,
+    /// Launcher features such as `is_demo_user` or `has_custom_resolution`.
+    pub features: Option<HashMap<String, bool>>,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 #[serde(rename_all(deserialize = "camelCase"))]
 pub struct ManifestLibraryDownloads {
     pub artifact: Option<ManifestFile>,
+    /// Extra jars keyed by classifier, e.g. `natives-linux` or `sources`.
+    pub classifiers: Option<HashMap<String, ManifestFile>>,
+}
+
+/// Files to leave out when extracting a natives jar.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ManifestExtract {
+    #[serde(default)]
+    pub exclude: Vec<String>,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all(deserialize = "camelCase"))]
 pub struct ManifestLibrary {
+    /// Missing for libraries of modded profiles, which only give a maven `url`.
+    #[serde(default)]
     pub downloads: ManifestLibraryDownloads,
     pub name: String,
+    /// Base URL of the maven repository hosting a library without `downloads`.
+    pub url: Option<String>,
     pub rules: Option<Vec<ManifestRule>>,
+    /// Natives classifier per OS name; may contain `${arch}`.
+    pub natives: Option<HashMap<String, String>>,
+    pub extract: Option<ManifestExtract>,
+}
+
+/// One or several values of an argument.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum ManifestArgumentValue {
+    Single(String),
+    Many(Vec<String>),
+}
+
+/// A game or JVM argument of the `arguments` object, possibly guarded by rules.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ManifestArgument {
+    Plain(String),
+    Conditional {
+        rules: Vec<ManifestRule>,
+        value: ManifestArgumentValue,
+    },
+}
+
+/// Arguments of versions since 1.13, which replaced `minecraftArguments`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ManifestArguments {
+    #[serde(default)]
+    pub game: Vec<ManifestArgument>,
+    #[serde(default)]
+    pub jvm: Vec<ManifestArgument>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ManifestLoggingFile {
+    pub id: String,
+    pub sha1: String,
+    pub size: u64,
+    pub url: String,
+}
+
+/// A logging configuration and the JVM argument to use it, `${path}` standing for its path.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ManifestLoggingEntry {
+    pub argument: String,
+    pub file: ManifestLoggingFile,
+    /// Format of the configuration, `log4j2-xml` for every version so far.
+    #[serde(rename = "type")]
+    pub type_: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ManifestLogging {
+    pub client: Option<ManifestLoggingEntry>,
+}
+
+impl ManifestLoggingEntry {
+    /// Where the configuration is installed: `assets/log_configs/<id>` in the game directory.
+    pub fn config_path(&self, game_path: &Path) -> PathBuf {
+        // Only the file name of the id is used, so it can't point outside the directory.
+        let name = Path::new(&self.file.id).file_name().unwrap_or_default();
+        game_path.join("assets").join("log_configs").join(name)
+    }
+
+    /// The JVM argument enabling the configuration installed in `game_path`.
+    pub fn jvm_argument(&self, game_path: &Path) -> String {
+        self.argument
+            .replace("${path}", &self.config_path(game_path).to_string_lossy())
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
@@ -70,17 +177,32 @@ pub enum VersionType {
     OldAlpha,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all(deserialize = "camelCase"))]
 pub struct Manifest {
+    /// Arguments of versions since 1.13.
+    pub arguments: Option<ManifestArguments>,
+    /// Empty, like `assets`, `downloads` and `java_version`, for a modded profile
+    /// which inherits it from the version in `inherits_from`.
+    #[serde(default)]
     pub asset_index: ManifestAssetIndex,
+    #[serde(default)]
     pub assets: String,
+    #[serde(default)]
     pub compliance_level: i8,
+    #[serde(default)]
     pub downloads: ManifestDownloads,
     pub id: String,
+    /// Id of the version this one extends, as used by modded profiles.
+    pub inherits_from: Option<String>,
+    #[serde(default)]
     pub java_version: ManifestComponent,
     pub libraries: Vec<ManifestLibrary>,
+    pub logging: Option<ManifestLogging>,
     pub main_class: String,
+    /// Space separated game arguments of versions before 1.13.
+    pub minecraft_arguments: Option<String>,
+    #[serde(default)]
     pub minimum_launcher_version: i8,
     pub release_time: String,
     pub time: String,
@@ -99,13 +221,17 @@ pub fn read_manifest_from_file(file: &str) -> Result<Manifest, ManifestError> {
     Ok(manifest)
 }
 
-impl ToString for VersionType {
-    fn to_string(&self) -> String {
-        match *self {
-            VersionType::Release => String::from("Release"),
-            VersionType::Snapshot => String::from("Snapshot"),
-            VersionType::OldAlpha | VersionType::OldBeta => String::from("Old"),
-        }
+impl std::fmt::Display for VersionType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match *self {
+                VersionType::Release => "Release",
+                VersionType::Snapshot => "Snapshot",
+                VersionType::OldAlpha | VersionType::OldBeta => "Old",
+            }
+        )
     }
 }
 
@@ -113,7 +239,16 @@ impl ToString for VersionType {
 mod tests {
     use serde::{Deserialize, Serialize};
 
-    use super::VersionType;
+    use super::{
+        read_manifest_from_str, ManifestArgument, ManifestArgumentValue, RuleAction, VersionType,
+    };
+
+    const OLD_ALPHA: &str = include_str!("../tests/fixtures/versions/a1.0.4.json");
+    const LEGACY: &str = include_str!("../tests/fixtures/versions/1.12.2.json");
+    const ARGUMENTS: &str = include_str!("../tests/fixtures/versions/1.13.json");
+    const SNAPSHOT: &str = include_str!("../tests/fixtures/versions/24w14a.json");
+    const FABRIC: &str =
+        include_str!("../tests/fixtures/versions/fabric-loader-0.15.9-1.20.4.json");
 
     #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
     #[serde(rename_all(deserialize = "camelCase"))]
@@ -157,4 +292,141 @@ mod tests {
         assert!(json.is_ok());
         assert_eq!(json.unwrap(), expected_st);
     }
+
+    #[test]
+    fn old_alpha_manifest() {
+        let manifest = read_manifest_from_str(OLD_ALPHA).unwrap();
+
+        assert_eq!(manifest.type_, VersionType::OldAlpha);
+        assert_eq!(manifest.compliance_level, 0);
+        assert!(manifest.downloads.server.is_none());
+        assert!(manifest.arguments.is_none());
+        assert!(manifest.logging.is_none());
+        assert!(manifest
+            .minecraft_arguments
+            .unwrap()
+            .contains("AlphaVanillaTweaker"));
+
+        let platform = &manifest.libraries[1];
+        assert_eq!(platform.natives.as_ref().unwrap()["osx"], "natives-osx");
+        assert_eq!(platform.extract.as_ref().unwrap().exclude, ["META-INF/"]);
+        let rules = platform.rules.as_ref().unwrap();
+        assert_eq!(rules[0].action, RuleAction::Allow);
+        assert_eq!(rules[1].action, RuleAction::Disallow);
+        assert_eq!(rules[1].os.as_ref().unwrap().name.as_deref(), Some("osx"));
+        let classifiers = platform.downloads.classifiers.as_ref().unwrap();
+        assert_eq!(classifiers["natives-linux"].size, 569061);
+    }
+
+    #[test]
+    fn legacy_manifest_with_logging_and_arch_natives() {
+        let manifest = read_manifest_from_str(LEGACY).unwrap();
+
+        assert!(manifest.arguments.is_none());
+        assert!(manifest.minecraft_arguments.is_some());
+        assert_eq!(manifest.asset_index.total_size, 129570069);
+
+        let logging = manifest.logging.unwrap().client.unwrap();
+        assert_eq!(logging.argument, "-Dlog4j.configurationFile=${path}");
+        assert_eq!(logging.file.id, "client-1.12.xml");
+        assert_eq!(logging.type_, "log4j2-xml");
+
+        let twitch = &manifest.libraries[1];
+        assert!(twitch.downloads.artifact.is_none());
+        assert_eq!(
+            twitch.natives.as_ref().unwrap()["windows"],
+            "natives-windows-${arch}"
+        );
+    }
+
+    #[test]
+    fn arguments_with_rules() {
+        let manifest = read_manifest_from_str(ARGUMENTS).unwrap();
+        let arguments = manifest.arguments.unwrap();
+
+        assert!(manifest.minecraft_arguments.is_none());
+        assert!(matches!(&arguments.game[0], ManifestArgument::Plain(a) if a == "--username"));
+        match &arguments.game[6] {
+            ManifestArgument::Conditional { rules, value } => {
+                assert!(rules[0].features.as_ref().unwrap()["is_demo_user"]);
+                assert_eq!(value, &ManifestArgumentValue::Single("--demo".into()));
+            }
+            a => panic!("unexpected argument {a:?}"),
+        }
+        match &arguments.game[7] {
+            ManifestArgument::Conditional { value, .. } => {
+                assert!(matches!(value, ManifestArgumentValue::Many(v) if v.len() == 4));
+            }
+            a => panic!("unexpected argument {a:?}"),
+        }
+        match &arguments.jvm[2] {
+            ManifestArgument::Conditional { rules, .. } => {
+                let os = rules[0].os.as_ref().unwrap();
+                assert_eq!(os.version.as_deref(), Some("^10\\."));
+            }
+            a => panic!("unexpected argument {a:?}"),
+        }
+        match &arguments.jvm[3] {
+            ManifestArgument::Conditional { rules, .. } => {
+                assert_eq!(rules[0].os.as_ref().unwrap().arch.as_deref(), Some("x86"));
+            }
+            a => panic!("unexpected argument {a:?}"),
+        }
+    }
+
+    #[test]
+    fn current_snapshot_manifest() {
+        let manifest = read_manifest_from_str(SNAPSHOT).unwrap();
+
+        assert_eq!(manifest.type_, VersionType::Snapshot);
+        assert_eq!(manifest.compliance_level, 1);
+        assert_eq!(manifest.java_version.major_version, 21);
+        assert!(manifest.downloads.client_mappings.is_some());
+        assert!(manifest.inherits_from.is_none());
+        assert!(manifest.arguments.unwrap().game.iter().any(|a| matches!(
+            a,
+            ManifestArgument::Conditional { rules, .. }
+                if rules[0].features.as_ref().unwrap().contains_key("has_quick_plays_support")
+        )));
+        assert_eq!(
+            manifest.libraries[1].name,
+            "org.lwjgl:lwjgl:3.3.3:natives-linux"
+        );
+        assert!(manifest.libraries[1].natives.is_none());
+    }
+
+    #[test]
+    fn modded_profile_fields() {
+        let mut raw: serde_json::Value = serde_json::from_str(SNAPSHOT).unwrap();
+        raw["inheritsFrom"] = "24w14a".into();
+        raw["libraries"] = serde_json::json!([{
+            "name": "net.fabricmc:fabric-loader:0.15.9",
+            "url": "https://maven.fabricmc.net/"
+        }]);
+        let manifest = read_manifest_from_str(&raw.to_string()).unwrap();
+
+        assert_eq!(manifest.inherits_from.as_deref(), Some("24w14a"));
+        let loader = &manifest.libraries[0];
+        assert_eq!(loader.url.as_deref(), Some("https://maven.fabricmc.net/"));
+        assert!(loader.downloads.artifact.is_none());
+    }
+
+    #[test]
+    fn fabric_profile() {
+        let manifest = read_manifest_from_str(FABRIC).unwrap();
+
+        assert_eq!(manifest.inherits_from.as_deref(), Some("1.20.4"));
+        assert_eq!(
+            manifest.main_class,
+            "net.fabricmc.loader.impl.launch.knot.KnotClient"
+        );
+        assert!(manifest.assets.is_empty());
+        assert!(manifest.asset_index.url.is_empty());
+        assert!(manifest.downloads.client.url.is_empty());
+        assert_eq!(manifest.java_version.major_version, 0);
+        assert_eq!(manifest.arguments.unwrap().jvm.len(), 1);
+        let loader = manifest.libraries.last().unwrap();
+        assert_eq!(loader.name, "net.fabricmc:fabric-loader:0.15.9");
+        assert_eq!(loader.url.as_deref(), Some("https://maven.fabricmc.net/"));
+    }
 }
@@ -0,0 +1,363 @@
+use std::{
+    io,
+    path::Path,
+    pin::Pin,
+    process::{ExitStatus, Stdio},
+    str::FromStr,
+    task::{Context, Poll},
+    time::SystemTime,
+};
+
+use crate::crash::{find_crash, CrashSummary};
+use futures::Stream;
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    process::{Child, Command},
+    sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Fatal,
+}
+
+impl FromStr for LogLevel {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "TRACE" => Ok(Self::Trace),
+            "DEBUG" => Ok(Self::Debug),
+            "INFO" => Ok(Self::Info),
+            "WARN" => Ok(Self::Warn),
+            "ERROR" => Ok(Self::Error),
+            "FATAL" => Ok(Self::Fatal),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A log event written by the game through Mojang's log4j2 XML layout.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogRecord {
+    pub level: LogLevel,
+    pub logger: String,
+    pub thread: String,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    pub message: String,
+    /// Stack trace of the exception logged with the message.
+    pub throwable: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// Something the game process wrote.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GameOutput {
+    Record(LogRecord),
+    /// A line that is not part of a log event, e.g. from the JVM itself.
+    Line {
+        stream: OutputStream,
+        line: String,
+    },
+}
+
+/// A running game, streaming its output as [`GameOutput`] until both of its pipes close.
+///
+/// Output is buffered while nobody reads it, so the process never blocks on a full pipe.
+pub struct GameProcess {
+    child: Child,
+    output: UnboundedReceiver<GameOutput>,
+    started: SystemTime,
+}
+
+impl GameProcess {
+    /// Spawns `command` with its stdout and stderr captured.
+    pub fn spawn(command: impl Into<Command>) -> io::Result<Self> {
+        let mut command = command.into();
+        let mut child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let started = SystemTime::now();
+        let (sender, output) = unbounded_channel();
+        if let Some(stdout) = child.stdout.take() {
+            tokio::spawn(capture(stdout, OutputStream::Stdout, sender.clone()));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            tokio::spawn(capture(stderr, OutputStream::Stderr, sender));
+        }
+
+        Ok(Self {
+            child,
+            output,
+            started,
+        })
+    }
+
+    pub fn id(&self) -> Option<u32> {
+        self.child.id()
+    }
+
+    /// Waits for the game to exit.
+    pub async fn wait(&mut self) -> io::Result<ExitStatus> {
+        self.child.wait().await
+    }
+
+    pub async fn kill(&mut self) -> io::Result<()> {
+        self.child.kill().await
+    }
+
+    pub fn started(&self) -> SystemTime {
+        self.started
+    }
+
+    /// The crash the game left in `game_path` since it was started, see [`find_crash`].
+    pub fn crash_summary(&self, game_path: &Path) -> io::Result<Option<CrashSummary>> {
+        find_crash(game_path, self.started)
+    }
+}
+
+impl Stream for GameProcess {
+    type Item = GameOutput;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.output.poll_recv(cx)
+    }
+}
+
+async fn capture(
+    pipe: impl AsyncRead + Unpin,
+    stream: OutputStream,
+    sender: UnboundedSender<GameOutput>,
+) {
+    let mut reader = BufReader::new(pipe);
+    let mut parser = LogParser::new(stream);
+    let mut buf = Vec::new();
+    // Lines are read as bytes: a JVM printing in the local code page must not end the capture.
+    while let Ok(read) = reader.read_until(b'\n', &mut buf).await {
+        if read == 0 {
+            break;
+        }
+        let line = String::from_utf8_lossy(&buf);
+        for output in parser.push(line.trim_end_matches(['\n', '\r'])) {
+            let _ = sender.send(output);
+        }
+        buf.clear();
+    }
+    for output in parser.finish() {
+        let _ = sender.send(output);
+    }
+}
+
+/// Splits the output of one stream into log events and plain lines.
+pub(crate) struct LogParser {
+    stream: OutputStream,
+    event: Vec<String>,
+}
+
+impl LogParser {
+    pub fn new(stream: OutputStream) -> Self {
+        Self {
+            stream,
+            event: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, line: &str) -> Vec<GameOutput> {
+        if self.event.is_empty() && !line.trim_start().starts_with("<log4j:Event") {
+            return vec![self.line(line)];
+        }
+
+        self.event.push(line.to_string());
+        if !line.contains("</log4j:Event>") {
+            return Vec::new();
+        }
+        let lines = std::mem::take(&mut self.event);
+        match parse_event(&lines.join("\n")) {
+            Some(record) => vec![GameOutput::Record(record)],
+            None => lines.iter().map(|l| self.line(l)).collect(),
+        }
+    }
+
+    /// Flushes an event cut short by the end of the stream.
+    pub fn finish(&mut self) -> Vec<GameOutput> {
+        std::mem::take(&mut self.event)
+            .iter()
+            .map(|l| self.line(l))
+            .collect()
+    }
+
+    fn line(&self, line: &str) -> GameOutput {
+        GameOutput::Line {
+            stream: self.stream,
+            line: line.to_string(),
+        }
+    }
+}
+
+fn parse_event(xml: &str) -> Option<LogRecord> {
+    let start = xml.find("<log4j:Event")? + "<log4j:Event".len();
+    let tag = &xml[start..start + xml[start..].find('>')?];
+    let attribute = |name: &str| {
+        let pattern = format!("{name}=\"");
+        let value = &tag[tag.find(&pattern)? + pattern.len()..];
+        Some(unescape(&value[..value.find('"')?]))
+    };
+
+    Some(LogRecord {
+        level: attribute("level")?.parse().ok()?,
+        logger: attribute("logger")?,
+        thread: attribute("thread").unwrap_or_default(),
+        timestamp: attribute("timestamp")?.parse().ok()?,
+        message: element(xml, "log4j:Message").unwrap_or_default(),
+        throwable: element(xml, "log4j:Throwable"),
+    })
+}
+
+/// The text of the first `<name>` element, CDATA or escaped.
+fn element(xml: &str, name: &str) -> Option<String> {
+    let open = format!("<{name}>");
+    let start = xml.find(&open)? + open.len();
+    let content = &xml[start..start + xml[start..].find(&format!("</{name}>"))?];
+
+    match content
+        .strip_prefix("<![CDATA[")
+        .and_then(|c| c.strip_suffix("]]>"))
+    {
+        Some(data) => Some(data.to_string()),
+        None => Some(unescape(content)),
+    }
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GameOutput, LogLevel, LogParser, OutputStream};
+
+    const EVENTS: &str = include_str!("../tests/fixtures/logs/client.xml");
+
+    fn parse(text: &str) -> Vec<GameOutput> {
+        let mut parser = LogParser::new(OutputStream::Stdout);
+        let mut output = text
+            .lines()
+            .flat_map(|l| parser.push(l))
+            .collect::<Vec<_>>();
+        output.extend(parser.finish());
+        output
+    }
+
+    fn records(output: &[GameOutput]) -> Vec<&super::LogRecord> {
+        output
+            .iter()
+            .filter_map(|o| match o {
+                GameOutput::Record(record) => Some(record),
+                GameOutput::Line { .. } => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_events_and_plain_lines() {
+        let output = parse(EVENTS);
+        let records = records(&output);
+
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].level, LogLevel::Info);
+        assert_eq!(records[0].logger, "net.minecraft.client.Minecraft");
+        assert_eq!(records[0].thread, "Render thread");
+        assert_eq!(records[0].timestamp, 1712147950123);
+        assert_eq!(records[0].message, "Setting user: Player");
+        assert_eq!(records[1].message, "Tried to load <missing> & failed");
+        assert_eq!(records[2].level, LogLevel::Error);
+        assert!(records[2]
+            .throwable
+            .as_deref()
+            .unwrap()
+            .starts_with("java.lang.IllegalStateException: boom\n\tat "));
+
+        assert_eq!(
+            output[0],
+            GameOutput::Line {
+                stream: OutputStream::Stdout,
+                line: "Picked up _JAVA_OPTIONS: -Xmx2G".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn truncated_event_falls_back_to_lines() {
+        let output =
+            parse("<log4j:Event logger=\"a\" timestamp=\"1\" level=\"INFO\">\n<log4j:Message>");
+        assert_eq!(output.len(), 2);
+        assert!(records(&output).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn supervises_a_fake_java() {
+        use futures::StreamExt;
+
+        use crate::client::test_server::temp_dir;
+
+        use super::GameProcess;
+
+        let dir = temp_dir("process");
+        let log = dir.join("client.xml");
+        std::fs::write(&log, EVENTS).unwrap();
+        let java = dir.join("java");
+        std::fs::write(
+            &java,
+            format!(
+                "printf 'caf\\351\\n'\ncat '{}'\necho 'Exception in thread main' >&2\nexit 3\n",
+                log.display()
+            ),
+        )
+        .unwrap();
+        // Run by `sh` rather than executed, which could fail with ETXTBSY while another test
+        // forks with the script still open for writing.
+        let mut command = std::process::Command::new("sh");
+        command.arg(&java);
+
+        let (output, status) = tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let mut process = GameProcess::spawn(command).unwrap();
+            let output = process.by_ref().collect::<Vec<_>>().await;
+            (output, process.wait().await.unwrap())
+        });
+
+        assert_eq!(status.code(), Some(3));
+        // Events after a line that isn't UTF-8 still come through.
+        assert_eq!(records(&output).len(), 3);
+        assert!(output.contains(&GameOutput::Line {
+            stream: OutputStream::Stdout,
+            line: "caf\u{fffd}".to_string()
+        }));
+        assert!(output.contains(&GameOutput::Line {
+            stream: OutputStream::Stderr,
+            line: "Exception in thread main".to_string()
+        }));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
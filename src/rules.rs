@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+
+use regex::Regex;
+
+use crate::manifest::{
+    ManifestArgument, ManifestArgumentValue, ManifestArguments, ManifestLibrary, ManifestRule,
+    ManifestRuleOs, RuleAction,
+};
+
+/// The operating system and architecture a version is evaluated for, named the way version
+/// JSONs name them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Platform {
+    /// `windows`, `osx` or `linux`.
+    pub os: String,
+    /// OS version matched by the `os.version` regex of rules; empty if unknown.
+    pub version: String,
+    /// `x86`, `x86_64` or `arm64`.
+    pub arch: String,
+}
+
+impl Platform {
+    pub fn new(os: &str, arch: &str) -> Self {
+        Self {
+            os: os.to_string(),
+            version: String::new(),
+            arch: arch.to_string(),
+        }
+    }
+
+    /// The platform this process runs on.
+    pub fn current() -> Self {
+        let os = match std::env::consts::OS {
+            "macos" => "osx",
+            os => os,
+        };
+        let arch = match std::env::consts::ARCH {
+            "aarch64" => "arm64",
+            arch => arch,
+        };
+        let version = match std::env::consts::OS {
+            "linux" => std::fs::read_to_string("/proc/sys/kernel/osrelease")
+                .map(|v| v.trim().to_string())
+                .unwrap_or_default(),
+            _ => String::new(),
+        };
+
+        Self {
+            version,
+            ..Self::new(os, arch)
+        }
+    }
+
+    pub fn with_version(&mut self, version: &str) -> &mut Self {
+        self.version = version.to_string();
+        self
+    }
+}
+
+impl Default for Platform {
+    fn default() -> Self {
+        Self::current()
+    }
+}
+
+/// Everything rules are evaluated against: the target platform and the launcher features
+/// (`is_demo_user`, `has_custom_resolution`, `has_quick_plays_support`, ...) that are enabled.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RuleEnvironment {
+    pub platform: Platform,
+    pub features: HashMap<String, bool>,
+}
+
+impl RuleEnvironment {
+    pub fn new(platform: Platform) -> Self {
+        Self {
+            platform,
+            features: HashMap::new(),
+        }
+    }
+
+    pub fn with_feature(&mut self, feature: &str, enabled: bool) -> &mut Self {
+        self.features.insert(feature.to_string(), enabled);
+        self
+    }
+
+    fn feature(&self, feature: &str) -> bool {
+        self.features.get(feature).copied().unwrap_or(false)
+    }
+}
+
+impl ManifestRuleOs {
+    pub fn matches(&self, platform: &Platform) -> bool {
+        let version = || match &self.version {
+            // An invalid pattern can't be satisfied.
+            Some(pattern) => Regex::new(pattern).is_ok_and(|r| r.is_match(&platform.version)),
+            None => true,
+        };
+
+        self.name.as_ref().is_none_or(|n| *n == platform.os)
+            && self.arch.as_ref().is_none_or(|a| *a == platform.arch)
+            && version()
+    }
+}
+
+impl ManifestRule {
+    /// Whether the rule applies to `env`, regardless of its action.
+    pub fn matches(&self, env: &RuleEnvironment) -> bool {
+        self.os.as_ref().is_none_or(|os| os.matches(&env.platform))
+            && self
+                .features
+                .iter()
+                .flatten()
+                .all(|(feature, value)| env.feature(feature) == *value)
+    }
+}
+
+/// Evaluates `rules` the way the official launcher does: with no rules everything is allowed,
+/// otherwise it is disallowed unless a matching rule allows it, the last matching rule winning.
+pub fn is_allowed(rules: &[ManifestRule], env: &RuleEnvironment) -> bool {
+    if rules.is_empty() {
+        return true;
+    }
+    rules
+        .iter()
+        .rev()
+        .find(|rule| rule.matches(env))
+        .is_some_and(|rule| rule.action == RuleAction::Allow)
+}
+
+impl ManifestLibrary {
+    pub fn is_allowed(&self, env: &RuleEnvironment) -> bool {
+        is_allowed(self.rules.as_deref().unwrap_or_default(), env)
+    }
+}
+
+impl ManifestArgument {
+    /// The values of the argument, or nothing if its rules disallow it.
+    pub fn values(&self, env: &RuleEnvironment) -> Vec<&str> {
+        match self {
+            Self::Plain(value) => vec![value],
+            Self::Conditional { rules, .. } if !is_allowed(rules, env) => Vec::new(),
+            Self::Conditional { value, .. } => match value {
+                ManifestArgumentValue::Single(value) => vec![value],
+                ManifestArgumentValue::Many(values) => values.iter().map(String::as_str).collect(),
+            },
+        }
+    }
+}
+
+impl ManifestArguments {
+    /// The game arguments allowed in `env`, placeholders left as they are.
+    pub fn game_arguments(&self, env: &RuleEnvironment) -> Vec<&str> {
+        self.game.iter().flat_map(|a| a.values(env)).collect()
+    }
+
+    /// The JVM arguments allowed in `env`, placeholders left as they are.
+    pub fn jvm_arguments(&self, env: &RuleEnvironment) -> Vec<&str> {
+        self.jvm.iter().flat_map(|a| a.values(env)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::manifest::read_manifest_from_str;
+
+    use super::{is_allowed, Platform, RuleEnvironment};
+
+    const LEGACY: &str = include_str!("../tests/fixtures/versions/a1.0.4.json");
+    const ARGUMENTS: &str = include_str!("../tests/fixtures/versions/1.13.json");
+
+    fn rules(raw: &str) -> Vec<crate::manifest::ManifestRule> {
+        serde_json::from_str(raw).unwrap()
+    }
+
+    fn env(os: &str, arch: &str) -> RuleEnvironment {
+        RuleEnvironment::new(Platform::new(os, arch))
+    }
+
+    #[test]
+    fn last_matching_rule_wins() {
+        let rules =
+            rules(r#"[{"action": "allow"}, {"action": "disallow", "os": {"name": "osx"}}]"#);
+        assert!(is_allowed(&rules, &env("linux", "x86_64")));
+        assert!(!is_allowed(&rules, &env("osx", "arm64")));
+        assert!(is_allowed(&[], &env("osx", "arm64")));
+
+        let only_windows = self::rules(r#"[{"action": "allow", "os": {"name": "windows"}}]"#);
+        assert!(!is_allowed(&only_windows, &env("linux", "x86_64")));
+        assert!(is_allowed(&only_windows, &env("windows", "x86_64")));
+    }
+
+    #[test]
+    fn os_version_arch_and_features() {
+        let version =
+            rules(r#"[{"action": "allow", "os": {"name": "windows", "version": "^10\\."}}]"#);
+        let mut windows = env("windows", "x86");
+        assert!(!is_allowed(&version, &windows));
+        windows.platform.with_version("10.0");
+        assert!(is_allowed(&version, &windows));
+
+        let arch = rules(r#"[{"action": "allow", "os": {"arch": "x86"}}]"#);
+        assert!(is_allowed(&arch, &windows));
+        assert!(!is_allowed(&arch, &env("windows", "x86_64")));
+
+        let demo = rules(r#"[{"action": "allow", "features": {"is_demo_user": true}}]"#);
+        let mut linux = env("linux", "x86_64");
+        assert!(!is_allowed(&demo, &linux));
+        linux.with_feature("is_demo_user", true);
+        assert!(is_allowed(&demo, &linux));
+    }
+
+    #[test]
+    fn libraries_and_arguments_are_filtered() {
+        let manifest = read_manifest_from_str(LEGACY).unwrap();
+        let osx = env("osx", "x86_64");
+        let allowed = manifest
+            .libraries
+            .iter()
+            .filter(|l| l.is_allowed(&osx))
+            .map(|l| l.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(allowed, ["net.minecraft:launchwrapper:1.6"]);
+
+        let arguments = read_manifest_from_str(ARGUMENTS)
+            .unwrap()
+            .arguments
+            .unwrap();
+        let mut osx = env("osx", "x86_64");
+        osx.with_feature("has_custom_resolution", true);
+        assert_eq!(
+            arguments.game_arguments(&osx)[6..],
+            [
+                "--width",
+                "${resolution_width}",
+                "--height",
+                "${resolution_height}"
+            ]
+        );
+        assert_eq!(
+            arguments.jvm_arguments(&osx),
+            [
+                "-XstartOnFirstThread",
+                "-Djava.library.path=${natives_directory}",
+                "-cp",
+                "${classpath}"
+            ]
+        );
+    }
+}